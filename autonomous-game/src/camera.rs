@@ -0,0 +1,585 @@
+//! The game camera, a `Camera2D` that follows a target around the map.
+//!
+//! A frame is drawn in two passes: `begin_world` before anything placed in
+//! world pixels, then `begin_hud` before screen-space UI. Effects such as
+//! screen shake are layered on top, see [`Effect`].
+//!
+//! The world pass is drawn one texel per world pixel into an offscreen
+//! canvas, which `begin_hud` scales up by a whole number of physical pixels
+//! and centers on the screen, with black bars around what doesn't divide.
+
+use macroquad::prelude::*;
+
+use crate::input::{Action, Input};
+
+mod effects;
+
+pub use effects::{Effect, Effects};
+
+// Screen points per world pixel, `min_zoom` also keeps the map filling the screen
+const MIN_ZOOM: f32 = 1.0;
+const MAX_ZOOM: f32 = 6.0;
+const DEFAULT_ZOOM: f32 = 2.5;
+// Zoom factor per frame of a zoom action held down, the step actions step
+// whole pixel scales
+const KEY_ZOOM: f32 = 1.02;
+// Letterbox bars during cinematic pans, as a fraction of the screen height
+const PAN_LETTERBOX: f32 = 0.1;
+
+/// How the camera follows its target.
+#[derive(Clone, Copy, Debug)]
+pub struct Follow {
+    /// How fast the camera catches up, per second. Higher is snappier.
+    pub stiffness: f32,
+    /// Half size of the rect, in world pixels, the target can move in
+    /// without the camera moving
+    pub dead_zone: Vec2,
+    /// How far ahead of a moving target to look, in world pixels
+    pub look_ahead: f32,
+}
+
+impl Default for Follow {
+    fn default() -> Self {
+        Self {
+            stiffness: 5.0,
+            dead_zone: vec2(24.0, 16.0),
+            look_ahead: 24.0,
+        }
+    }
+}
+
+// A cinematic move to a point and back to the follow target
+#[derive(Debug)]
+struct Pan {
+    from: Vec2,
+    to: Vec2,
+    duration: f32,
+    hold: f32,
+    elapsed: f32,
+}
+
+#[derive(Debug)]
+pub struct GameCamera {
+    /// World point at the center of the screen
+    pub position: Vec2,
+    // where the follow logic looks, before pans and clamping
+    focus: Vec2,
+    // in screen points, see `dpi_scale`
+    viewport_size: Vec2,
+    // physical pixels per screen point
+    dpi_scale: f32,
+    /// Screen points per world pixel, as asked for by the zoom input. It is
+    /// drawn rounded to a whole number of physical pixels, see `pixel_scale`.
+    pub zoom: f32,
+    pub follow: Follow,
+    pub effects: Effects,
+    // the camera never shows anything outside, usually the whole map
+    bounds: Option<Rect>,
+    pan: Option<Pan>,
+    // distance between the two fingers of a pinch last frame
+    pinch: Option<f32>,
+    // the world pass, created on first use and again when its size changes
+    canvas: Option<RenderTarget>,
+}
+
+// Where the world pass goes, see `GameCamera::layout`
+struct Layout {
+    // world pixel at the top left of the canvas
+    origin: Vec2,
+    // canvas size in texels, one per world pixel
+    size: UVec2,
+    // part of the canvas on screen, in texels
+    source: Rect,
+    // where it's drawn, in screen points
+    dest: Rect,
+}
+
+impl GameCamera {
+    pub fn new(viewport_size: Vec2) -> Self {
+        Self {
+            position: Vec2::ZERO,
+            focus: Vec2::ZERO,
+            viewport_size,
+            dpi_scale: 1.0,
+            zoom: DEFAULT_ZOOM,
+            follow: Follow::default(),
+            effects: Effects::default(),
+            bounds: None,
+            pan: None,
+            pinch: None,
+            canvas: None,
+        }
+    }
+
+    /// Follows window resizes, orientation changes and moves between
+    /// screens of different densities.
+    pub fn update_viewport_size(&mut self) {
+        self.resize(vec2(screen_width(), screen_height()), screen_dpi_scale());
+    }
+
+    /// `viewport_size` is in screen points, `dpi_scale` physical pixels per
+    /// point.
+    pub fn resize(&mut self, viewport_size: Vec2, dpi_scale: f32) {
+        self.viewport_size = viewport_size;
+        self.dpi_scale = dpi_scale.max(f32::EPSILON);
+        self.zoom = self.zoom.clamp(self.min_zoom(), MAX_ZOOM);
+        self.position = self.clamp(self.position);
+    }
+
+    pub fn set_bounds(&mut self, bounds: Rect) {
+        self.bounds = Some(bounds);
+        self.zoom = self.zoom.clamp(self.min_zoom(), MAX_ZOOM);
+        self.position = self.clamp(self.position);
+    }
+
+    /// Jumps to `target` without smoothing, e.g. after a teleport.
+    pub fn snap_to(&mut self, target: Vec2) {
+        self.focus = target;
+        self.pan = None;
+        self.position = self.clamp(target);
+    }
+
+    /// Moves towards `target`, looking ahead along `heading`, a unit vector
+    /// or zero when the target stands still.
+    pub fn update(&mut self, dt: f32, target: Vec2, heading: Vec2) {
+        self.effects.update(dt);
+
+        // Only move once the target leaves the dead zone, then keep it on its edge
+        let desired = target + heading * self.follow.look_ahead;
+        let offset = desired - self.focus;
+        let dead_zone = self.follow.dead_zone;
+        let goal = self.focus + offset - offset.clamp(-dead_zone, dead_zone);
+
+        // Exponential smoothing, independent of the frame rate
+        let t = 1.0 - (-self.follow.stiffness * dt).exp();
+        self.focus = self.focus.lerp(goal, t);
+
+        let position = match &mut self.pan {
+            Some(pan) => {
+                pan.elapsed += dt;
+                let back = pan.duration + pan.hold;
+                if pan.elapsed < pan.duration {
+                    pan.from.lerp(pan.to, ease(pan.elapsed / pan.duration))
+                } else if pan.elapsed < back {
+                    pan.to
+                } else if pan.elapsed < back + pan.duration {
+                    pan.to
+                        .lerp(self.focus, ease((pan.elapsed - back) / pan.duration))
+                } else {
+                    self.pan = None;
+                    self.effects.push(Effect::Letterbox {
+                        amount: 0.0,
+                        duration: 0.5,
+                    });
+                    self.focus
+                }
+            }
+            None => self.focus,
+        };
+        self.position = self.clamp(position);
+    }
+
+    /// Cinematic move: pans to `point` over `duration` seconds, stays there
+    /// for `hold` seconds and pans back to the follow target, letterboxed.
+    pub fn pan_to(&mut self, point: Vec2, duration: f32, hold: f32) {
+        self.effects.push(Effect::Letterbox {
+            amount: PAN_LETTERBOX,
+            duration: 0.5,
+        });
+        self.pan = Some(Pan {
+            from: self.position,
+            to: point,
+            duration: duration.max(f32::EPSILON),
+            hold,
+            elapsed: 0.0,
+        });
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the world point under
+    /// `anchor`, in screen pixels, in place.
+    pub fn zoom_by(&mut self, factor: f32, anchor: Vec2) {
+        let before = self.screen_to_world(anchor);
+        self.zoom = (self.zoom * factor).clamp(self.min_zoom(), MAX_ZOOM);
+        let shift = before - self.screen_to_world(anchor);
+        self.focus += shift;
+        self.position = self.clamp(self.position + shift);
+    }
+
+    /// The zoom step actions, the mouse wheel by default, zoom at the
+    /// pointer, the other zoom actions at the screen center and two finger
+    /// pinch at the pinch center.
+    pub fn handle_zoom_input(&mut self, input: &Input) {
+        for (action, step) in [(Action::ZoomInStep, 1.0), (Action::ZoomOutStep, -1.0)] {
+            if input.is_pressed(action) {
+                let scale = (self.pixel_scale() + step).max(1.0);
+                self.zoom_by(scale / self.dpi_scale / self.zoom, input.pointer);
+            }
+        }
+
+        let center = self.viewport_size / 2.0;
+        if input.is_down(Action::ZoomIn) {
+            self.zoom_by(KEY_ZOOM, center);
+        }
+        if input.is_down(Action::ZoomOut) {
+            self.zoom_by(1.0 / KEY_ZOOM, center);
+        }
+
+        if let [a, b] = input.touches.as_slice() {
+            let distance = a.position.distance(b.position);
+            if let Some(previous) = self.pinch.filter(|previous| *previous > 0.0) {
+                self.zoom_by(distance / previous, (a.position + b.position) / 2.0);
+            }
+            self.pinch = Some(distance);
+        } else {
+            self.pinch = None;
+        }
+    }
+
+    // Smallest zoom that still fills the screen with the bounds
+    fn min_zoom(&self) -> f32 {
+        match self.bounds {
+            Some(bounds) if bounds.w > 0.0 && bounds.h > 0.0 => MIN_ZOOM
+                .max(self.viewport_size.x / bounds.w)
+                .max(self.viewport_size.y / bounds.h)
+                .min(MAX_ZOOM),
+            _ => MIN_ZOOM,
+        }
+    }
+
+    /// Physical pixels per world pixel: the zoom rounded to a whole number,
+    /// so every world pixel is the same size on screen, but never so small
+    /// that the bounds stop filling it.
+    pub fn pixel_scale(&self) -> f32 {
+        let fill = (self.min_zoom() * self.dpi_scale - 1e-3).ceil();
+        (self.zoom * self.dpi_scale).round().max(fill).max(1.0)
+    }
+
+    // Screen points per world pixel, as drawn
+    fn scale(&self) -> f32 {
+        self.pixel_scale() / self.dpi_scale
+    }
+
+    // Whole world pixels that fit on screen
+    fn view_size(&self) -> Vec2 {
+        (self.viewport_size * self.dpi_scale / self.pixel_scale())
+            .floor()
+            .max(Vec2::ONE)
+    }
+
+    // Closest center that keeps the view inside the bounds, or the bounds'
+    // center on axes where they are smaller than the view
+    fn clamp(&self, position: Vec2) -> Vec2 {
+        let Some(bounds) = self.bounds else {
+            return position;
+        };
+        let half = self.view_size() / 2.0;
+        let axis = |position: f32, min: f32, max: f32, half: f32| {
+            if max - min < half * 2.0 {
+                (min + max) / 2.0
+            } else {
+                position.clamp(min + half, max - half)
+            }
+        };
+        vec2(
+            axis(position.x, bounds.left(), bounds.right(), half.x),
+            axis(position.y, bounds.top(), bounds.bottom(), half.y),
+        )
+    }
+
+    // The canvas covers the view plus a texel, so the camera can sit
+    // between world pixels and the canvas is shifted by whole physical
+    // pixels instead
+    fn layout(&self) -> Layout {
+        let view = self.view_size();
+        let top_left = self.position + self.effects.shake().0 - view / 2.0;
+        let origin = top_left.floor();
+        let pixel_scale = self.pixel_scale();
+        let shift = ((top_left - origin) * pixel_scale).round() / pixel_scale;
+
+        let physical = self.viewport_size * self.dpi_scale;
+        let margin = ((physical - view * pixel_scale) / 2.0).floor() / self.dpi_scale;
+        let size = view * self.scale();
+        Layout {
+            origin,
+            size: (view + Vec2::ONE).as_uvec2(),
+            source: Rect::new(shift.x, shift.y, view.x, view.y),
+            dest: Rect::new(margin.x, margin.y, size.x, size.y),
+        }
+    }
+
+    /// Following draw calls are in world pixels, on the canvas.
+    pub fn begin_world(&mut self) {
+        let layout = self.layout();
+        let canvas = match self.canvas.take() {
+            Some(canvas) if canvas.texture.size().as_uvec2() == layout.size => canvas,
+            _ => {
+                let canvas = render_target(layout.size.x, layout.size.y);
+                canvas.texture.set_filter(FilterMode::Nearest);
+                canvas
+            }
+        };
+
+        // one texel per world pixel, y pointing down like the screen
+        let size = layout.size.as_vec2();
+        set_camera(&Camera2D {
+            target: layout.origin + size / 2.0,
+            rotation: self.effects.shake().1,
+            zoom: vec2(2.0 / size.x, 2.0 / size.y),
+            render_target: Some(canvas.clone()),
+            ..Default::default()
+        });
+        clear_background(WHITE);
+        self.canvas = Some(canvas);
+    }
+
+    /// Draws the canvas, then following draw calls are in screen points.
+    pub fn begin_hud(&self) {
+        set_default_camera();
+        clear_background(BLACK);
+        if let Some(canvas) = &self.canvas {
+            let layout = self.layout();
+            draw_texture_ex(
+                &canvas.texture,
+                layout.dest.x,
+                layout.dest.y,
+                WHITE,
+                DrawTextureParams {
+                    source: Some(layout.source),
+                    dest_size: Some(layout.dest.size()),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Where the world is drawn, without the shake so clicks land where the
+    /// player meant.
+    pub fn world_to_screen(&self, world_position: Vec2) -> Vec2 {
+        (world_position - self.position) * self.scale() + self.viewport_size / 2.0
+    }
+
+    pub fn screen_to_world(&self, screen_position: Vec2) -> Vec2 {
+        (screen_position - self.viewport_size / 2.0) / self.scale() + self.position
+    }
+
+    /// Part of the world on screen, without the shake.
+    #[cfg(test)]
+    pub fn view(&self) -> Rect {
+        let size = self.view_size();
+        let top_left = self.position - size / 2.0;
+        Rect::new(top_left.x, top_left.y, size.x, size.y)
+    }
+
+    /// Part of the world drawn on the canvas, which is larger than `view`
+    /// while the screen shakes: moved by the shake, and grown to the box
+    /// around the canvas turned by the shake's angle.
+    pub fn drawn_view(&self) -> Rect {
+        let layout = self.layout();
+        let size = layout.size.as_vec2();
+        let center = layout.origin + size / 2.0;
+        let (sin, cos) = self.effects.shake().1.abs().to_radians().sin_cos();
+        let turned = vec2(size.x * cos + size.y * sin, size.x * sin + size.y * cos);
+        let top_left = center - turned / 2.0;
+        Rect::new(top_left.x, top_left.y, turned.x, turned.y)
+    }
+}
+
+// Smoothstep, slow at both ends
+fn ease(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 800x600 screen showing 266x200 world pixels, the default zoom is
+    // drawn at 3 physical pixels per world pixel
+    fn camera() -> GameCamera {
+        let mut camera = GameCamera::new(vec2(800.0, 600.0));
+        camera.snap_to(vec2(500.0, 500.0));
+        camera
+    }
+
+    #[test]
+    fn targets_inside_the_dead_zone_dont_move_the_camera() {
+        let mut camera = camera();
+        camera.update(1.0, vec2(520.0, 490.0), Vec2::ZERO);
+        assert_eq!(camera.position, vec2(500.0, 500.0));
+    }
+
+    #[test]
+    fn the_camera_eases_towards_the_target() {
+        let mut camera = camera();
+        let target = vec2(700.0, 500.0);
+        camera.update(1.0 / 60.0, target, Vec2::ZERO);
+        assert!(camera.position.x > 500.0 && camera.position.x < 520.0);
+
+        for _ in 0..300 {
+            camera.update(1.0 / 60.0, target, Vec2::ZERO);
+        }
+        // the target ends up on the edge of the dead zone
+        assert!((camera.position.x - (700.0 - 24.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn the_camera_looks_ahead_of_a_moving_target() {
+        let mut camera = camera();
+        for _ in 0..300 {
+            camera.update(1.0 / 60.0, vec2(500.0, 500.0), vec2(0.0, 1.0));
+        }
+        // 24px ahead, minus the 16px dead zone
+        assert!((camera.position.y - 508.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn the_view_stays_inside_the_bounds() {
+        let mut camera = camera();
+        camera.set_bounds(Rect::new(0.0, 0.0, 1000.0, 1000.0));
+        camera.snap_to(vec2(10.0, 990.0));
+        let view = camera.view();
+        assert_eq!(view.point(), vec2(0.0, 800.0));
+
+        // bounds smaller than the view are centered, at any zoom
+        camera.set_bounds(Rect::new(0.0, 0.0, 100.0, 1000.0));
+        camera.snap_to(vec2(10.0, 500.0));
+        assert_eq!(camera.position.x, 50.0);
+    }
+
+    #[test]
+    fn zooming_keeps_the_anchor_in_place() {
+        let mut camera = camera();
+        let anchor = vec2(700.0, 100.0);
+        let world = camera.screen_to_world(anchor);
+
+        camera.zoom_by(1.5, anchor);
+        assert_eq!(camera.zoom, 3.75);
+        assert!((camera.screen_to_world(anchor) - world).length() < 1e-3);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut camera = camera();
+        camera.zoom_by(100.0, Vec2::ZERO);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+
+        // the map always fills the screen
+        camera.set_bounds(Rect::new(0.0, 0.0, 400.0, 1000.0));
+        camera.zoom_by(0.01, Vec2::ZERO);
+        assert_eq!(camera.zoom, 2.0);
+    }
+
+    #[test]
+    fn wheel_steps_and_pinches_zoom() {
+        let mut camera = camera();
+        let mut input = Input::default();
+        input.press(Action::ZoomInStep);
+        camera.handle_zoom_input(&input);
+        assert_eq!(camera.pixel_scale(), 4.0);
+
+        // fingers going from 100 to 125 points apart zoom by a quarter, the
+        // world between them stays put
+        let zoom = camera.zoom;
+        let finger = |id, x| Touch {
+            id,
+            phase: TouchPhase::Moved,
+            position: vec2(x, 300.0),
+        };
+        let center = vec2(400.0, 300.0);
+        let mut input = Input::default();
+        input.touches = vec![finger(1, 350.0), finger(2, 450.0)];
+        camera.handle_zoom_input(&input);
+        let anchor = camera.screen_to_world(center);
+        input.touches = vec![finger(1, 337.5), finger(2, 462.5)];
+        camera.handle_zoom_input(&input);
+        assert!((camera.zoom - zoom * 1.25).abs() < 1e-4);
+        assert!((camera.screen_to_world(center) - anchor).length() < 1e-3);
+    }
+
+    #[test]
+    fn world_pixels_are_whole_physical_pixels() {
+        let mut camera = camera();
+        assert_eq!(camera.pixel_scale(), 3.0);
+
+        // a retina screen has twice the pixels in the same points
+        camera.resize(vec2(800.0, 600.0), 2.0);
+        assert_eq!(camera.pixel_scale(), 5.0);
+        assert_eq!(camera.view().size(), vec2(320.0, 240.0));
+
+        // never smaller than what fills the bounds
+        camera.set_bounds(Rect::new(0.0, 0.0, 1000.0, 500.0));
+        camera.zoom_by(0.01, Vec2::ZERO);
+        assert_eq!(camera.pixel_scale(), 3.0);
+        assert!(camera.view().h <= 500.0);
+    }
+
+    #[test]
+    fn the_canvas_is_centered_between_bars() {
+        let mut camera = camera();
+        // 266x200 world pixels at 3x leave 2 physical pixels across
+        let layout = camera.layout();
+        assert_eq!(layout.size, uvec2(267, 201));
+        assert_eq!(layout.dest, Rect::new(1.0, 0.0, 798.0, 600.0));
+        assert_eq!(layout.source, Rect::new(0.0, 0.0, 266.0, 200.0));
+
+        // between world pixels the canvas shifts by whole physical pixels
+        camera.snap_to(vec2(500.4, 500.0));
+        let layout = camera.layout();
+        assert_eq!(layout.origin, vec2(367.0, 400.0));
+        assert!((layout.source.x - 1.0 / 3.0).abs() < 1e-5);
+
+        // clicks land on what's drawn under them
+        let corner = camera.screen_to_world(layout.dest.point());
+        assert!((corner - camera.view().point()).length() < 0.5);
+    }
+
+    #[test]
+    fn the_drawn_view_covers_the_shaking_canvas() {
+        let mut camera = camera();
+        assert!(camera.drawn_view().contains(camera.view().point()));
+
+        camera.effects.push(Effect::Shake(1.0));
+        camera.effects.update(0.05);
+        let (offset, angle) = camera.effects.shake();
+        assert!(offset.length() > 1.0 && angle.abs() > 0.1);
+
+        // corners of the canvas as turned around its center by the shake
+        let layout = camera.layout();
+        let size = layout.size.as_vec2();
+        let center = layout.origin + size / 2.0;
+        let turn = Vec2::from_angle(angle.to_radians());
+        let drawn = camera.drawn_view();
+        let grown = Rect::new(
+            drawn.x - 1e-3,
+            drawn.y - 1e-3,
+            drawn.w + 2e-3,
+            drawn.h + 2e-3,
+        );
+        for corner in [
+            vec2(-1.0, -1.0),
+            vec2(1.0, -1.0),
+            vec2(1.0, 1.0),
+            vec2(-1.0, 1.0),
+        ] {
+            let corner = center + turn.rotate(corner * size / 2.0);
+            assert!(grown.contains(corner), "{corner} outside {drawn:?}");
+        }
+    }
+
+    #[test]
+    fn pans_go_to_the_point_and_come_back() {
+        let mut camera = camera();
+        camera.pan_to(vec2(600.0, 500.0), 1.0, 2.0);
+
+        camera.update(0.5, vec2(500.0, 500.0), Vec2::ZERO);
+        assert_eq!(camera.position, vec2(550.0, 500.0));
+        camera.update(1.0, vec2(500.0, 500.0), Vec2::ZERO);
+        assert_eq!(camera.position, vec2(600.0, 500.0));
+        camera.update(2.0, vec2(500.0, 500.0), Vec2::ZERO);
+        assert_eq!(camera.position, vec2(550.0, 500.0));
+        camera.update(0.6, vec2(500.0, 500.0), Vec2::ZERO);
+        assert_eq!(camera.position, vec2(500.0, 500.0));
+        assert!(camera.pan.is_none());
+    }
+}
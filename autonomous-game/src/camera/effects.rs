@@ -0,0 +1,189 @@
+use macroquad::prelude::*;
+
+// Shake at full trauma, in world pixels and degrees
+const MAX_SHAKE_OFFSET: f32 = 8.0;
+const MAX_SHAKE_ANGLE: f32 = 2.0;
+// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
+
+/// Something to add to the camera's effect stack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// Adds trauma, between 0 and 1. The shake grows with the square of the
+    /// trauma, so small hits barely move the screen and big ones add up.
+    Shake(f32),
+    /// Tints the screen with `color`, fading out over `duration` seconds
+    Flash { color: Color, duration: f32 },
+    /// Fades the screen towards `alpha` black over `duration` seconds, and
+    /// stays there until the next `Fade`
+    Fade { alpha: f32, duration: f32 },
+    /// Slides black bars over `amount` of the screen height, top and bottom
+    /// each, over `duration` seconds
+    Letterbox { amount: f32, duration: f32 },
+}
+
+// A value easing linearly to `to`
+#[derive(Clone, Copy, Debug, Default)]
+struct Tween {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Tween {
+    fn value(&self) -> f32 {
+        if self.elapsed >= self.duration {
+            return self.to;
+        }
+        self.from + (self.to - self.from) * self.elapsed / self.duration
+    }
+
+    fn retarget(&mut self, to: f32, duration: f32) {
+        *self = Tween {
+            from: self.value(),
+            to,
+            duration,
+            elapsed: 0.0,
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Flash {
+    color: Color,
+    duration: f32,
+    elapsed: f32,
+}
+
+/// Camera effects, each decaying on its own clock. Shake moves the world
+/// pass, the others are drawn over the HUD pass.
+#[derive(Debug, Default)]
+pub struct Effects {
+    trauma: f32,
+    time: f32,
+    flashes: Vec<Flash>,
+    fade: Tween,
+    letterbox: Tween,
+}
+
+impl Effects {
+    pub fn push(&mut self, effect: Effect) {
+        match effect {
+            Effect::Shake(trauma) => self.trauma = (self.trauma + trauma).clamp(0.0, 1.0),
+            Effect::Flash { color, duration } => self.flashes.push(Flash {
+                color,
+                duration,
+                elapsed: 0.0,
+            }),
+            Effect::Fade { alpha, duration } => self.fade.retarget(alpha, duration),
+            Effect::Letterbox { amount, duration } => self.letterbox.retarget(amount, duration),
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        for flash in &mut self.flashes {
+            flash.elapsed += dt;
+        }
+        self.flashes.retain(|flash| flash.elapsed < flash.duration);
+        self.fade.elapsed += dt;
+        self.letterbox.elapsed += dt;
+    }
+
+    /// Offset of the camera target, in world pixels, and rotation in degrees.
+    pub fn shake(&self) -> (Vec2, f32) {
+        let shake = self.trauma * self.trauma;
+        if shake == 0.0 {
+            return (Vec2::ZERO, 0.0);
+        }
+        // Sums of sines at unrelated frequencies, smooth but never repeating
+        let noise = |seed: f32| {
+            ((self.time * 29.0 + seed).sin() + (self.time * 47.0 + seed * 2.3).sin()) / 2.0
+        };
+        (
+            vec2(noise(0.0), noise(10.0)) * MAX_SHAKE_OFFSET * shake,
+            noise(20.0) * MAX_SHAKE_ANGLE * shake,
+        )
+    }
+
+    /// Opacity of the fade to black.
+    pub fn fade_alpha(&self) -> f32 {
+        self.fade.value()
+    }
+
+    /// Draws the overlays in screen pixels, see `GameCamera::begin_hud`.
+    pub fn draw(&self) {
+        let (width, height) = (screen_width(), screen_height());
+        for flash in &self.flashes {
+            let mut color = flash.color;
+            color.a *= 1.0 - flash.elapsed / flash.duration;
+            draw_rectangle(0.0, 0.0, width, height, color);
+        }
+
+        let bar = self.letterbox.value() * height;
+        if bar > 0.0 {
+            draw_rectangle(0.0, 0.0, width, bar, BLACK);
+            draw_rectangle(0.0, height - bar, width, bar, BLACK);
+        }
+
+        let alpha = self.fade_alpha();
+        if alpha > 0.0 {
+            draw_rectangle(0.0, 0.0, width, height, Color::new(0.0, 0.0, 0.0, alpha));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trauma_adds_up_and_decays() {
+        let mut effects = Effects::default();
+        effects.push(Effect::Shake(0.4));
+        effects.push(Effect::Shake(0.4));
+        effects.update(0.1);
+        assert!((effects.trauma - 0.65).abs() < 1e-6);
+
+        effects.push(Effect::Shake(5.0));
+        assert_eq!(effects.trauma, 1.0);
+        effects.update(1.0);
+        assert_eq!(effects.shake(), (Vec2::ZERO, 0.0));
+    }
+
+    #[test]
+    fn fades_ease_to_their_alpha_and_stay() {
+        let mut effects = Effects::default();
+        effects.push(Effect::Fade {
+            alpha: 1.0,
+            duration: 0.5,
+        });
+        effects.update(0.25);
+        assert_eq!(effects.fade_alpha(), 0.5);
+        effects.update(1.0);
+        assert_eq!(effects.fade_alpha(), 1.0);
+
+        // fading back starts from where the fade is
+        effects.push(Effect::Fade {
+            alpha: 0.0,
+            duration: 0.5,
+        });
+        effects.update(0.25);
+        assert_eq!(effects.fade_alpha(), 0.5);
+    }
+
+    #[test]
+    fn flashes_expire() {
+        let mut effects = Effects::default();
+        effects.push(Effect::Flash {
+            color: WHITE,
+            duration: 0.2,
+        });
+        effects.update(0.1);
+        assert_eq!(effects.flashes.len(), 1);
+        effects.update(0.1);
+        assert!(effects.flashes.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+//! Screen to remap the keys, reached from the main menu.
+
+use macroquad::prelude::*;
+use macroquad::ui::{root_ui, widgets};
+
+use crate::input::{Action, InputMap};
+
+// Every action, in the order they're listed
+const ACTIONS: &[(Action, &str)] = &[
+    (Action::MoveUp, "Move up"),
+    (Action::MoveDown, "Move down"),
+    (Action::MoveLeft, "Move left"),
+    (Action::MoveRight, "Move right"),
+    (Action::MoveTo, "Walk to pointer"),
+    (Action::Interact, "Interact"),
+    (Action::ToggleBalance, "Show balance"),
+    (Action::ZoomIn, "Zoom in"),
+    (Action::ZoomOut, "Zoom out"),
+    (Action::ZoomInStep, "Zoom in a step"),
+    (Action::ZoomOutStep, "Zoom out a step"),
+];
+// Layout in screen points
+const LEFT: f32 = 40.0;
+const TOP: f32 = 90.0;
+const ROW_HEIGHT: f32 = 36.0;
+const BINDINGS_X: f32 = 280.0;
+const FONT_SIZE: f32 = 28.0;
+const HELP_Y: f32 = TOP + ACTIONS.len() as f32 * ROW_HEIGHT + 40.0;
+const BUTTON_SIZE: Vec2 = vec2(160.0, 60.0);
+const BUTTON_GAP: f32 = 16.0;
+
+#[derive(Default)]
+pub struct ControlsMenu {
+    selected: usize,
+    // waiting for the new key of the selected action
+    capturing: bool,
+}
+
+impl ControlsMenu {
+    /// Returns `true` when the player leaves the screen. Changes are saved
+    /// as they're made.
+    pub fn update(&mut self, map: &mut InputMap) -> bool {
+        // Buttons for screens without a keyboard. Escape and Backspace do
+        // the same.
+        let back = button(0, if self.capturing { "Cancel" } else { "Back" });
+        let defaults = !self.capturing && button(1, "Defaults");
+
+        if self.capturing {
+            if back {
+                self.capturing = false;
+            } else if let Some(key) = get_last_key_pressed() {
+                self.capturing = false;
+                // Escape cancels, so it can't be bound
+                if key != KeyCode::Escape {
+                    map.bind_key(ACTIONS[self.selected].0, key);
+                    map.save();
+                }
+            }
+            return false;
+        }
+
+        // Fixed keys, so no binding can lock the player out of this screen
+        if back || is_key_pressed(KeyCode::Escape) {
+            return true;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + ACTIONS.len() - 1) % ACTIONS.len();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % ACTIONS.len();
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            self.capturing = true;
+        }
+        if defaults || is_key_pressed(KeyCode::Backspace) {
+            *map = InputMap::default();
+            map.save();
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let row = ((mouse_position().1 - TOP) / ROW_HEIGHT).floor();
+            if row >= 0.0 && (row as usize) < ACTIONS.len() {
+                self.selected = row as usize;
+                self.capturing = true;
+            }
+        }
+        false
+    }
+
+    pub fn draw(&self, map: &InputMap) {
+        clear_background(Color::new(0.1, 0.1, 0.12, 1.0));
+        draw_text("Controls", LEFT, TOP - 30.0, 40.0, WHITE);
+
+        for (row, &(action, name)) in ACTIONS.iter().enumerate() {
+            let y = TOP + row as f32 * ROW_HEIGHT + FONT_SIZE;
+            let selected = row == self.selected;
+            let color = if selected { YELLOW } else { WHITE };
+            let bindings = if selected && self.capturing {
+                "press a key".to_owned()
+            } else {
+                map.bindings(action)
+                    .iter()
+                    .map(|binding| binding.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            draw_text(name, LEFT, y, FONT_SIZE, color);
+            draw_text(&bindings, BINDINGS_X, y, FONT_SIZE, color);
+        }
+
+        draw_text(
+            "Enter or click to change, Backspace for the defaults, Escape to go back",
+            LEFT,
+            HELP_Y,
+            20.0,
+            GRAY,
+        );
+    }
+}
+
+// Whether button `index` of the row under the help was clicked this frame
+fn button(index: usize, label: &str) -> bool {
+    let x = LEFT + index as f32 * (BUTTON_SIZE.x + BUTTON_GAP);
+    widgets::Button::new(label)
+        .position(vec2(x, HELP_Y + 20.0))
+        .size(BUTTON_SIZE)
+        .ui(&mut root_ui())
+}
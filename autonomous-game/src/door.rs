@@ -1,5 +1,5 @@
 use macroquad::prelude::*;
-use platformer::World;
+use platformer::{Solid, World};
 
 use crate::platformer;
 
@@ -14,6 +14,8 @@ pub enum DoorState {
 pub struct Door {
     texture: Texture2D,
     position: Vec2,
+    // blocks the doorway while the door is closed
+    solid: Solid,
     state: DoorState,
     frame: usize,
     frames_count: usize,
@@ -22,14 +24,16 @@ pub struct Door {
 }
 
 impl Door {
-    pub async fn new(position: Vec2, frames_count: usize) -> Self {
+    pub async fn new(position: Vec2, frames_count: usize, world: &mut World) -> Self {
         let texture = load_texture("assets/DoorAnimationSpriteSheet.png")
             .await
             .unwrap();
+        let solid = world.add_solid(position, 16, 16);
 
         Self {
             texture,
             position,
+            solid,
             state: DoorState::Closed,
             frame: 0,
             frames_count,
@@ -38,11 +42,8 @@ impl Door {
         }
     }
 
-    pub fn get_position(&self) -> Vec2 {
-        self.position
-    }
-
-    pub fn update(&mut self, dt: f32) {
+    /// Returns `true` when a closing door shuts.
+    pub fn update(&mut self, dt: f32) -> bool {
         self.animation_timer += dt;
 
         if self.animation_timer >= self.frame_time {
@@ -61,32 +62,29 @@ impl Door {
                         self.frame -= 1;
                     } else {
                         self.state = DoorState::Closed;
+                        return true;
                     }
                 }
                 _ => {} // No animation for Closed or Open states
             }
         }
+        false
     }
 
-    pub fn draw_door(&self, camera: &crate::GameCamera) {
+    /// Draws in world pixels, see `GameCamera::begin_world`.
+    pub fn draw_door(&self) {
         // Calculate the source rectangle from the tileset
         let src_x = self.frame as f32 * 16.0;
         let src_rect = Rect::new(src_x, 0.0, 16.0, 16.0);
         // Assuming tiles are arranged horizontally
-        // Convert world position to screen position using camera
-        let screen_pos = camera.world_to_screen(self.position);
-        // Apply camera zoom to the destination size
-        let scaled_size = vec2(16.0 * camera.zoom, 16.0 * camera.zoom);
-
-        // Draw the door with camera transformations
         draw_texture_ex(
             &self.texture,
-            screen_pos.x,
-            screen_pos.y,
+            self.position.x,
+            self.position.y,
             WHITE,
             DrawTextureParams {
                 source: Some(src_rect),
-                dest_size: Some(scaled_size),
+                dest_size: Some(vec2(16.0, 16.0)),
                 ..Default::default()
             },
         );
@@ -96,17 +94,62 @@ impl Door {
         match self.state {
             DoorState::Closed => {
                 self.state = DoorState::Opening;
-                world.add_solid(Vec2::new(784.0, 560.0), 16, 16);
+                world.set_solid_collidable(self.solid, false);
             }
-            DoorState::Open => {
+            // stays open while someone stands in the doorway
+            DoorState::Open if close(world, self.solid) => {
                 self.state = DoorState::Closing;
-                world.remove_solid(Vec2::new(784.0, 560.0));
             }
             _ => {} // Don't interrupt animations in progress
         }
     }
 
+    /// Takes the door's solid out of the world, for when its map is unloaded.
+    pub fn remove(&self, world: &mut World) {
+        world.remove_solid_handle(self.solid);
+    }
+
+    // Bottom of the door, for depth sorting
+    pub fn base_y(&self) -> f32 {
+        self.position.y + 16.0
+    }
+
     pub fn is_animating(&self) -> bool {
         matches!(self.state, DoorState::Opening | DoorState::Closing)
     }
 }
+
+// Makes the doorway block again, unless someone stands in it and would be
+// stuck inside the door. Returns whether it closed.
+fn close(world: &mut World, solid: Solid) -> bool {
+    let pos = world.solid_pos(solid);
+    if !world
+        .actors_in_rect(Rect::new(pos.x, pos.y, 16.0, 16.0))
+        .is_empty()
+    {
+        return false;
+    }
+    world.set_solid_collidable(solid, true);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doors_dont_close_on_someone_in_the_doorway() {
+        let mut world = World::new();
+        let solid = world.add_solid(vec2(32.0, 32.0), 16, 16);
+        world.set_solid_collidable(solid, false);
+        let actor = world.add_actor(vec2(40.0, 36.0), 16, 16);
+
+        assert!(!close(&mut world, solid));
+        assert!(!world.solid_collidable(solid));
+
+        // touching the doorway from outside is fine
+        world.set_actor_position(actor, vec2(48.0, 36.0));
+        assert!(close(&mut world, solid));
+        assert!(world.solid_collidable(solid));
+    }
+}
@@ -0,0 +1,143 @@
+//! The first gamepad connected, which macroquad doesn't read.
+//!
+//! On the web the page reads it through the Gamepad API. Elsewhere gilrs
+//! does, when the `gilrs` feature is on, and no pad is ever connected
+//! without it.
+
+use crate::input::GamepadButton;
+
+/// Buttons held on the first gamepad this frame and the one before.
+#[derive(Default)]
+pub struct Gamepad {
+    // bit `i` is button `i` of the standard Gamepad API layout
+    held: u32,
+    previous: u32,
+    #[cfg(all(feature = "gilrs", not(target_arch = "wasm32")))]
+    gilrs: Gilrs,
+}
+
+// None when the platform's gamepad support fails to start
+#[cfg(all(feature = "gilrs", not(target_arch = "wasm32")))]
+struct Gilrs(Option<gilrs::Gilrs>);
+
+#[cfg(all(feature = "gilrs", not(target_arch = "wasm32")))]
+impl Default for Gilrs {
+    fn default() -> Self {
+        let gilrs = gilrs::Gilrs::new();
+        if let Err(err) = &gilrs {
+            eprintln!("no gamepads: {err}");
+        }
+        Self(gilrs.ok())
+    }
+}
+
+impl Gamepad {
+    /// Reads the buttons, once a frame.
+    pub fn update(&mut self) {
+        let held = self.read();
+        self.advance(held);
+    }
+
+    fn advance(&mut self, held: u32) {
+        self.previous = self.held;
+        self.held = held;
+    }
+
+    pub fn is_down(&self, button: GamepadButton) -> bool {
+        self.held & bit(button) != 0
+    }
+
+    /// Down this frame and not the one before
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.is_down(button) && self.previous & bit(button) == 0
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read(&mut self) -> u32 {
+        autonomous_game::read_gamepad_buttons()
+    }
+
+    #[cfg(all(feature = "gilrs", not(target_arch = "wasm32")))]
+    fn read(&mut self) -> u32 {
+        use crate::input::GAMEPAD_BUTTONS;
+
+        let Some(gilrs) = &mut self.gilrs.0 else {
+            return 0;
+        };
+        // gilrs only updates pads while its events are taken
+        while gilrs.next_event().is_some() {}
+        let Some((_, pad)) = gilrs.gamepads().next() else {
+            return 0;
+        };
+        GAMEPAD_BUTTONS
+            .iter()
+            .filter(|&&button| pad.is_pressed(gilrs_button(button)))
+            .fold(0, |held, &button| held | bit(button))
+    }
+
+    #[cfg(not(any(feature = "gilrs", target_arch = "wasm32")))]
+    fn read(&mut self) -> u32 {
+        0
+    }
+}
+
+// Index in the standard Gamepad API layout, which the page reports
+fn bit(button: GamepadButton) -> u32 {
+    let index = match button {
+        GamepadButton::South => 0,
+        GamepadButton::East => 1,
+        GamepadButton::West => 2,
+        GamepadButton::North => 3,
+        GamepadButton::LeftShoulder => 4,
+        GamepadButton::RightShoulder => 5,
+        GamepadButton::Select => 8,
+        GamepadButton::Start => 9,
+        GamepadButton::DPadUp => 12,
+        GamepadButton::DPadDown => 13,
+        GamepadButton::DPadLeft => 14,
+        GamepadButton::DPadRight => 15,
+    };
+    1 << index
+}
+
+#[cfg(all(feature = "gilrs", not(target_arch = "wasm32")))]
+fn gilrs_button(button: GamepadButton) -> gilrs::Button {
+    // gilrs calls the shoulder buttons triggers, and the triggers
+    // LeftTrigger2 and RightTrigger2
+    match button {
+        GamepadButton::South => gilrs::Button::South,
+        GamepadButton::East => gilrs::Button::East,
+        GamepadButton::West => gilrs::Button::West,
+        GamepadButton::North => gilrs::Button::North,
+        GamepadButton::DPadUp => gilrs::Button::DPadUp,
+        GamepadButton::DPadDown => gilrs::Button::DPadDown,
+        GamepadButton::DPadLeft => gilrs::Button::DPadLeft,
+        GamepadButton::DPadRight => gilrs::Button::DPadRight,
+        GamepadButton::LeftShoulder => gilrs::Button::LeftTrigger,
+        GamepadButton::RightShoulder => gilrs::Button::RightTrigger,
+        GamepadButton::Start => gilrs::Button::Start,
+        GamepadButton::Select => gilrs::Button::Select,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons_are_pressed_only_on_the_frame_they_go_down() {
+        let mut pad = Gamepad::default();
+        pad.advance(bit(GamepadButton::South));
+        assert!(pad.is_down(GamepadButton::South));
+        assert!(pad.is_pressed(GamepadButton::South));
+        assert!(!pad.is_down(GamepadButton::North));
+
+        pad.advance(bit(GamepadButton::South) | bit(GamepadButton::DPadLeft));
+        assert!(pad.is_down(GamepadButton::South));
+        assert!(!pad.is_pressed(GamepadButton::South));
+        assert!(pad.is_pressed(GamepadButton::DPadLeft));
+
+        pad.advance(0);
+        assert!(!pad.is_down(GamepadButton::DPadLeft));
+    }
+}
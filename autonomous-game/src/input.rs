@@ -0,0 +1,662 @@
+//! Named actions and the keys, buttons, touches and gamepad buttons bound to
+//! them.
+//!
+//! The game asks an [`Input`] snapshot which actions are held or were just
+//! pressed, never the devices. [`InputMap::poll`] takes the snapshot from an
+//! [`InputSource`], the real devices in the game or a fake one in tests.
+
+use crate::gamepad::Gamepad;
+use autonomous_game::{get_state, save_input_bindings};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    /// Walk to the pointer
+    MoveTo,
+    /// Open doors and use what's nearby
+    Interact,
+    ToggleBalance,
+    ZoomIn,
+    ZoomOut,
+    /// One more physical pixel per world pixel, at the pointer
+    ZoomInStep,
+    /// One less physical pixel per world pixel, at the pointer
+    ZoomOutStep,
+}
+
+/// Gamepad buttons by position, south is A on Xbox pads and Cross on
+/// PlayStation ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftShoulder,
+    RightShoulder,
+    Start,
+    Select,
+}
+
+pub const GAMEPAD_BUTTONS: &[GamepadButton] = &[
+    GamepadButton::South,
+    GamepadButton::East,
+    GamepadButton::West,
+    GamepadButton::North,
+    GamepadButton::DPadUp,
+    GamepadButton::DPadDown,
+    GamepadButton::DPadLeft,
+    GamepadButton::DPadRight,
+    GamepadButton::LeftShoulder,
+    GamepadButton::RightShoulder,
+    GamepadButton::Start,
+    GamepadButton::Select,
+];
+
+/// Which way the mouse wheel turns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wheel {
+    Up,
+    Down,
+}
+
+// Keys a binding can name, macroquad's key codes can't be listed otherwise
+const KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Escape,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::LeftShift,
+    KeyCode::RightShift,
+    KeyCode::LeftControl,
+    KeyCode::RightControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightAlt,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpAdd,
+    KeyCode::KpSubtract,
+    KeyCode::KpMultiply,
+    KeyCode::KpDivide,
+    KeyCode::KpEnter,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+];
+
+/// Something that can trigger an action. Saved as text, e.g. `Key:W`,
+/// `Mouse:Left`, `Wheel:Up`, `Touch` or `Gamepad:South`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Binding {
+    /// A key by its position on a US keyboard, so on the web `Key:W` is the
+    /// Z key of an AZERTY keyboard
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// The mouse wheel turning, pressed on every frame it turns
+    Wheel(Wheel),
+    /// Any finger on the screen
+    Touch,
+    /// A button of the first gamepad connected
+    Gamepad(GamepadButton),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "Key:{key:?}"),
+            Binding::Mouse(button) => write!(f, "Mouse:{button:?}"),
+            Binding::Wheel(wheel) => write!(f, "Wheel:{wheel:?}"),
+            Binding::Touch => write!(f, "Touch"),
+            Binding::Gamepad(button) => write!(f, "Gamepad:{button:?}"),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Names are the `Debug` output, as written by `Display`
+        fn named<T: fmt::Debug + Copy>(values: &[T], name: &str) -> Option<T> {
+            values
+                .iter()
+                .copied()
+                .find(|value| format!("{value:?}") == name)
+        }
+
+        let binding = match s.split_once(':') {
+            Some(("Key", name)) => named(KEYS, name).map(Binding::Key),
+            Some(("Mouse", name)) => named(
+                &[MouseButton::Left, MouseButton::Middle, MouseButton::Right],
+                name,
+            )
+            .map(Binding::Mouse),
+            Some(("Wheel", name)) => named(&[Wheel::Up, Wheel::Down], name).map(Binding::Wheel),
+            Some(("Gamepad", name)) => named(GAMEPAD_BUTTONS, name).map(Binding::Gamepad),
+            None if s == "Touch" => Some(Binding::Touch),
+            _ => None,
+        };
+        binding.ok_or_else(|| format!("unknown binding {s:?}"))
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> String {
+        binding.to_string()
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Where input comes from.
+pub trait InputSource {
+    fn is_down(&self, binding: Binding) -> bool;
+    /// Down this frame and not the one before
+    fn is_pressed(&self, binding: Binding) -> bool;
+    /// Where the mouse or the last finger down is, in screen points
+    fn pointer(&self) -> Vec2;
+    /// Fingers on the screen, in screen points
+    fn touches(&self) -> Vec<Touch>;
+}
+
+/// Macroquad's keyboard, mouse and touches, and the first gamepad.
+#[derive(Default)]
+pub struct Devices {
+    gamepad: Gamepad,
+}
+
+impl Devices {
+    /// Reads the gamepad, once a frame before polling.
+    pub fn update(&mut self) {
+        self.gamepad.update();
+    }
+}
+
+impl InputSource for Devices {
+    fn is_down(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => is_key_down(key),
+            Binding::Mouse(button) => is_mouse_button_down(button),
+            Binding::Wheel(_) => self.is_pressed(binding),
+            Binding::Touch => !touches().is_empty(),
+            Binding::Gamepad(button) => self.gamepad.is_down(button),
+        }
+    }
+
+    fn is_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => is_key_pressed(key),
+            Binding::Mouse(button) => is_mouse_button_pressed(button),
+            Binding::Wheel(Wheel::Up) => mouse_wheel().1 > 0.0,
+            Binding::Wheel(Wheel::Down) => mouse_wheel().1 < 0.0,
+            Binding::Touch => touches()
+                .iter()
+                .any(|touch| touch.phase == TouchPhase::Started),
+            Binding::Gamepad(button) => self.gamepad.is_pressed(button),
+        }
+    }
+
+    fn pointer(&self) -> Vec2 {
+        match self.touches().last() {
+            Some(touch) => touch.position,
+            None => Vec2::from(mouse_position()),
+        }
+    }
+
+    fn touches(&self) -> Vec<Touch> {
+        let mut touches = touches();
+        // touches are in physical pixels, unlike the mouse
+        for touch in &mut touches {
+            touch.position /= screen_dpi_scale();
+        }
+        touches
+    }
+}
+
+/// The actions held and pressed in one frame.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    down: HashSet<Action>,
+    pressed: HashSet<Action>,
+    /// Where the pointer is, in screen points
+    pub pointer: Vec2,
+    /// Fingers on the screen, in screen points
+    pub touches: Vec<Touch>,
+}
+
+impl Input {
+    pub fn is_down(&self, action: Action) -> bool {
+        self.down.contains(&action)
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    /// Holds `action` down, without pressing it this frame.
+    pub fn hold(&mut self, action: Action) {
+        self.down.insert(action);
+    }
+
+    /// Presses `action` this frame.
+    pub fn press(&mut self, action: Action) {
+        self.down.insert(action);
+        self.pressed.insert(action);
+    }
+
+    /// Lets go of `action`, as if none of its bindings were used.
+    pub fn release(&mut self, action: Action) {
+        self.down.remove(&action);
+        self.pressed.remove(&action);
+    }
+}
+
+/// Bindings of every action, remappable while the game runs. The page keeps
+/// them between sessions and hands them back with
+/// `autonomous_game::set_input_bindings`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap {
+    bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key, Mouse};
+        use GamepadButton as Pad;
+
+        let bindings = [
+            (
+                Action::MoveUp,
+                vec![Key(KeyCode::Up), Key(KeyCode::W), Gamepad(Pad::DPadUp)],
+            ),
+            (
+                Action::MoveDown,
+                vec![Key(KeyCode::Down), Key(KeyCode::S), Gamepad(Pad::DPadDown)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Key(KeyCode::Left), Key(KeyCode::A), Gamepad(Pad::DPadLeft)],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::Right),
+                    Key(KeyCode::D),
+                    Gamepad(Pad::DPadRight),
+                ],
+            ),
+            (
+                Action::MoveTo,
+                vec![Mouse(MouseButton::Left), Binding::Touch],
+            ),
+            (
+                Action::Interact,
+                vec![Key(KeyCode::Space), Key(KeyCode::E), Gamepad(Pad::South)],
+            ),
+            (
+                Action::ToggleBalance,
+                vec![Key(KeyCode::B), Gamepad(Pad::North)],
+            ),
+            (
+                Action::ZoomIn,
+                vec![
+                    Key(KeyCode::Equal),
+                    Key(KeyCode::KpAdd),
+                    Gamepad(Pad::RightShoulder),
+                ],
+            ),
+            (
+                Action::ZoomOut,
+                vec![
+                    Key(KeyCode::Minus),
+                    Key(KeyCode::KpSubtract),
+                    Gamepad(Pad::LeftShoulder),
+                ],
+            ),
+            (Action::ZoomInStep, vec![Binding::Wheel(Wheel::Up)]),
+            (Action::ZoomOutStep, vec![Binding::Wheel(Wheel::Down)]),
+        ];
+        Self {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl InputMap {
+    /// The saved bindings, or the defaults if there are none or they don't
+    /// parse.
+    pub fn load() -> Self {
+        let json = get_state().input_bindings.clone();
+        if json.is_empty() {
+            return Self::default();
+        }
+        Self::from_json(&json).unwrap_or_else(|err| {
+            warn!("ignoring saved input bindings: {}", err);
+            Self::default()
+        })
+    }
+
+    /// Hands the bindings to the page to save.
+    pub fn save(&self) {
+        let json = self.to_json();
+        save_input_bindings(&json);
+        get_state().input_bindings = json;
+    }
+
+    /// Picks up bindings the page changed since the last call.
+    pub fn reload_if_changed(&mut self) {
+        let changed = std::mem::take(&mut get_state().input_bindings_changed);
+        if changed {
+            *self = Self::load();
+            // hand back what's in use, defaults filled in
+            self.save();
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut map: InputMap = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        // actions added since the bindings were saved keep their defaults
+        for (action, bindings) in InputMap::default().bindings {
+            map.bindings.entry(action).or_insert(bindings);
+        }
+        Ok(map)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("bindings always serialize")
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Makes `key` the only key for `action`, keeping its other bindings.
+    /// The key moves from whichever action had it, so one key never does
+    /// two things.
+    pub fn bind_key(&mut self, action: Action, key: KeyCode) {
+        for bindings in self.bindings.values_mut() {
+            bindings.retain(|&binding| binding != Binding::Key(key));
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|binding| !matches!(binding, Binding::Key(_)));
+        bindings.insert(0, Binding::Key(key));
+    }
+
+    /// Which actions `source` triggers this frame.
+    pub fn poll(&self, source: &impl InputSource) -> Input {
+        let mut input = Input {
+            pointer: source.pointer(),
+            touches: source.touches(),
+            ..Default::default()
+        };
+        for (&action, bindings) in &self.bindings {
+            if bindings.iter().any(|&binding| source.is_pressed(binding)) {
+                input.press(action);
+            } else if bindings.iter().any(|&binding| source.is_down(binding)) {
+                input.hold(action);
+            }
+        }
+        // Every finger of a pinch lands like a tap, none of them walks
+        if input.touches.len() >= 2 {
+            input.release(Action::MoveTo);
+        }
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bindings held down, the ones pressed this frame and the fingers on
+    // the screen
+    #[derive(Default)]
+    struct FakeSource {
+        down: Vec<Binding>,
+        pressed: Vec<Binding>,
+        touches: Vec<Touch>,
+    }
+
+    impl InputSource for FakeSource {
+        fn is_down(&self, binding: Binding) -> bool {
+            self.down.contains(&binding) || self.pressed.contains(&binding)
+        }
+
+        fn is_pressed(&self, binding: Binding) -> bool {
+            self.pressed.contains(&binding)
+        }
+
+        fn pointer(&self) -> Vec2 {
+            vec2(10.0, 20.0)
+        }
+
+        fn touches(&self) -> Vec<Touch> {
+            self.touches.clone()
+        }
+    }
+
+    fn touch(id: u64, phase: TouchPhase) -> Touch {
+        Touch {
+            id,
+            phase,
+            position: vec2(id as f32 * 100.0, 50.0),
+        }
+    }
+
+    #[test]
+    fn any_binding_triggers_its_action() {
+        let map = InputMap::default();
+        let source = FakeSource {
+            down: vec![Binding::Key(KeyCode::W)],
+            pressed: vec![Binding::Touch],
+            ..Default::default()
+        };
+        let input = map.poll(&source);
+
+        assert!(input.is_down(Action::MoveUp) && !input.is_pressed(Action::MoveUp));
+        assert!(input.is_pressed(Action::MoveTo));
+        assert!(!input.is_down(Action::MoveDown));
+        assert_eq!(input.pointer, vec2(10.0, 20.0));
+    }
+
+    #[test]
+    fn pinching_doesnt_walk() {
+        let map = InputMap::default();
+        let tap = FakeSource {
+            pressed: vec![Binding::Touch],
+            touches: vec![touch(1, TouchPhase::Started)],
+            ..Default::default()
+        };
+        assert!(map.poll(&tap).is_pressed(Action::MoveTo));
+
+        // the second finger of a pinch lands, then both move
+        let pinch = FakeSource {
+            pressed: vec![Binding::Touch],
+            touches: vec![
+                touch(1, TouchPhase::Stationary),
+                touch(2, TouchPhase::Started),
+            ],
+            ..Default::default()
+        };
+        let input = map.poll(&pinch);
+        assert!(!input.is_down(Action::MoveTo));
+        assert_eq!(input.touches.len(), 2);
+        let pinch = FakeSource {
+            down: vec![Binding::Touch],
+            touches: vec![touch(1, TouchPhase::Moved), touch(2, TouchPhase::Moved)],
+            ..Default::default()
+        };
+        assert!(!map.poll(&pinch).is_down(Action::MoveTo));
+    }
+
+    #[test]
+    fn binding_a_key_moves_it() {
+        let mut map = InputMap::default();
+        map.bind_key(Action::Interact, KeyCode::S);
+        assert_eq!(
+            map.bindings(Action::Interact),
+            [
+                Binding::Key(KeyCode::S),
+                Binding::Gamepad(GamepadButton::South)
+            ]
+        );
+        assert!(!map
+            .bindings(Action::MoveDown)
+            .contains(&Binding::Key(KeyCode::S)));
+
+        let source = FakeSource {
+            pressed: vec![Binding::Key(KeyCode::Space)],
+            ..Default::default()
+        };
+        assert!(!map.poll(&source).is_pressed(Action::Interact));
+
+        // bindings other than keys stay
+        map.bind_key(Action::ZoomInStep, KeyCode::PageUp);
+        assert_eq!(
+            map.bindings(Action::ZoomInStep),
+            [Binding::Key(KeyCode::PageUp), Binding::Wheel(Wheel::Up)]
+        );
+    }
+
+    #[test]
+    fn bindings_round_trip_through_json() {
+        let mut map = InputMap::default();
+        map.bind_key(Action::ToggleBalance, KeyCode::Tab);
+        map.bind_key(Action::ZoomOutStep, KeyCode::PageDown);
+        let json = map.to_json();
+        assert!(
+            json.contains(r#""ToggleBalance":["Key:Tab","Gamepad:North"]"#),
+            "{json}"
+        );
+        assert!(
+            json.contains(r#""ZoomOutStep":["Key:PageDown","Wheel:Down"]"#),
+            "{json}"
+        );
+        assert_eq!(InputMap::from_json(&json).unwrap(), map);
+
+        // missing actions keep their defaults, unknown keys are errors
+        let map = InputMap::from_json(r#"{"Interact":["Key:F"]}"#).unwrap();
+        assert_eq!(map.bindings(Action::Interact), [Binding::Key(KeyCode::F)]);
+        assert_eq!(
+            map.bindings(Action::MoveTo),
+            InputMap::default().bindings(Action::MoveTo)
+        );
+        assert!(InputMap::from_json(r#"{"Interact":["Key:Nope"]}"#).is_err());
+    }
+
+    #[test]
+    fn every_binding_parses_back() {
+        let bindings = KEYS
+            .iter()
+            .map(|&key| Binding::Key(key))
+            .chain(
+                GAMEPAD_BUTTONS
+                    .iter()
+                    .map(|&button| Binding::Gamepad(button)),
+            )
+            .chain([
+                Binding::Mouse(MouseButton::Right),
+                Binding::Wheel(Wheel::Up),
+                Binding::Wheel(Wheel::Down),
+                Binding::Touch,
+            ]);
+        for binding in bindings {
+            assert_eq!(binding.to_string().parse(), Ok(binding));
+        }
+    }
+}
@@ -2,6 +2,9 @@ use lazy_static::lazy_static;
 use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 
+/// Collision world shared by the game and future NPCs.
+pub mod platformer;
+
 // utils
 #[wasm_bindgen]
 pub fn print(name: &str) {
@@ -28,6 +31,12 @@ extern "C" {
 
     #[wasm_bindgen(js_name = emitGameStart)]
     fn emit_game_start_();
+
+    #[wasm_bindgen(js_name = saveInputBindings)]
+    fn save_input_bindings_(input_bindings: &str);
+
+    #[wasm_bindgen(js_name = readGamepadButtons)]
+    fn read_gamepad_buttons_() -> u32;
 }
 
 // have to wrap the extern function
@@ -51,11 +60,24 @@ pub  fn emit_game_start(){
     emit_game_start_()
 }
 
+/// Has the page keep the bindings, as JSON, for the next session.
+pub fn save_input_bindings(input_bindings: &str) {
+    save_input_bindings_(input_bindings);
+}
+
+/// Buttons held on the page's first gamepad, bit `i` for button `i` of the
+/// standard Gamepad API layout.
+pub fn read_gamepad_buttons() -> u32 {
+    read_gamepad_buttons_()
+}
+
 lazy_static! {
     static ref SHARED_STATE: Mutex<SharedState> = Mutex::new(SharedState {
         balance: 0.0,
         sui_address: "".to_string(),
-        is_paid: false
+        is_paid: false,
+        input_bindings: "".to_string(),
+        input_bindings_changed: false,
     });
 }
 
@@ -63,6 +85,11 @@ pub struct SharedState {
     pub sui_address: String,
     pub balance: f32,
     pub is_paid: bool,
+    /// Key bindings as JSON, empty for the defaults. The page saves them
+    /// between sessions, see `save_input_bindings`.
+    pub input_bindings: String,
+    /// Set when the page replaces `input_bindings`
+    pub input_bindings_changed: bool,
 }
 
 pub fn get_state() -> std::sync::MutexGuard<'static, SharedState> {
@@ -98,3 +125,15 @@ pub fn update_is_paid(is_paid: bool) {
 pub fn get_is_paid() -> bool {
     get_state().is_paid
 }
+
+#[wasm_bindgen]
+pub fn set_input_bindings(input_bindings: String) {
+    let mut state = get_state();
+    state.input_bindings = input_bindings;
+    state.input_bindings_changed = true;
+}
+
+#[wasm_bindgen]
+pub fn get_input_bindings() -> String {
+    get_state().input_bindings.clone()
+}
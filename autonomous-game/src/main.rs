@@ -1,23 +1,48 @@
 use autonomous_game::{
-    emit_game_start, get_state, request_connect, request_disconnect, request_paid_transaction,
+    emit_game_start, get_state, platformer, request_connect, request_disconnect,
+    request_paid_transaction,
 };
 mod animated_gif;
+mod camera;
+mod controls;
 mod door;
-mod platformer;
+mod gamepad;
+mod input;
+mod map;
+mod scene;
+mod tiles;
+mod touch;
 
 use animated_gif::AnimatedBackground;
-use door::Door;
+use camera::{Effect, GameCamera};
+use controls::ControlsMenu;
+use input::{Action, Devices, Input, InputMap};
 use macroquad::prelude::*;
 use macroquad::ui::Skin;
 use macroquad::ui::{hash, root_ui};
-use macroquad_tiled::{self as tiled, Map};
+use map::LayerData;
 use platformer::*;
+use scene::{PlacedTile, Scene, Transition, DOOR_TRIGGER, INTERACT_TRIGGER, PORTAL_TRIGGER};
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use touch::TouchControls;
 
 const SPRITE_SIZE: f32 = 48.0;
 const ANIMATION_SPEED: f32 = 0.1;
+// Player collider is a 16x16 box centered on the player position
+const COLLIDER_OFFSET: Vec2 = vec2(8.0, 8.0);
+// Map the game starts on, see `scene::MAPS`
+const START_MAP: &str = "island";
+// Trauma of a door slamming shut, see `camera::Effect::Shake`
+const DOOR_SLAM: f32 = 0.4;
+// Seconds of a map's first visit pan each way, and at the pan point
+const PAN_DURATION: f32 = 1.5;
+const PAN_HOLD: f32 = 1.0;
+const PAID_FLASH: Color = Color::new(1.0, 0.9, 0.5, 0.6);
 
 enum GameState {
     MainMenu,
+    Controls,
     Playing,
 }
 
@@ -33,8 +58,6 @@ struct Resources {
     tilled_dirt_texture: Texture2D,
     farming_plants_texture: Texture2D,
     dialog_box_texture: Texture2D,
-    // map json
-    tiled_map_json: String,
     bg_animation: AnimatedBackground,
     // ui
     menu_texture: Image,
@@ -64,9 +87,6 @@ impl Resources {
         let button_texture = load_image("./assets/ui/Button.png").await?;
         let clicked_button_texture = load_image("./assets/ui/ClickedButton.png").await?;
 
-        // Create a simple Tiled map JSON
-        let tiled_map_json = load_string("assets/map.json").await.unwrap();
-
         // animated background gif
         let bg_animation = AnimatedBackground::load("./assets/animated-gif/", 64).await;
 
@@ -81,7 +101,6 @@ impl Resources {
             tilled_dirt_texture,
             farming_plants_texture,
             dialog_box_texture,
-            tiled_map_json,
             bg_animation,
             menu_texture,
             button_texture,
@@ -91,44 +110,23 @@ impl Resources {
 
         Ok(resources)
     }
-}
-
-#[derive(Debug)]
-struct GameCamera {
-    position: Vec2,
-    viewport_size: Vec2,
-    zoom: f32,
-}
-
-impl GameCamera {
-    fn new() -> Self {
-        Self {
-            position: Vec2::new(0.0, 0.0),
-            viewport_size: Vec2::new(screen_width(), screen_height()),
-            zoom: 2.5,
-        }
-    }
-
-    fn update_viewport_size(&mut self) {
-        self.viewport_size = Vec2::new(screen_width(), screen_height());
-    }
-
-    fn update(&mut self, target_position: Vec2) {
-        self.position = target_position;
-    }
 
-    fn world_to_screen(&self, world_position: Vec2) -> Vec2 {
-        Vec2::new(
-            (world_position.x - self.position.x) * self.zoom + self.viewport_size.x / 2.0,
-            (world_position.y - self.position.y) * self.zoom + self.viewport_size.y / 2.0,
-        )
-    }
-
-    fn screen_to_world(&self, screen_position: Vec2) -> Vec2 {
-        Vec2::new(
-            (screen_position.x - self.viewport_size.x / 2.0) / self.zoom + self.position.x,
-            (screen_position.y - self.viewport_size.y / 2.0) / self.zoom + self.position.y,
-        )
+    // Tileset images by the name the maps use for them
+    fn tilesets(&self) -> Vec<(&'static str, Texture2D)> {
+        vec![
+            ("Grass.png", self.grass_texture.clone()),
+            ("Hills.png", self.hills_texture.clone()),
+            ("Water.png", self.water_texture.clone()),
+            (
+                "WoodenHouseWall.png",
+                self.wooden_house_wall_texture.clone(),
+            ),
+            ("DoorAnimation.png", self.door_animation_texture.clone()),
+            ("BasicFurniture.png", self.basic_furniture_texture.clone()),
+            ("Plants.png", self.plants_texture.clone()),
+            ("TilledDirt.png", self.tilled_dirt_texture.clone()),
+            ("FarmingPlants.png", self.farming_plants_texture.clone()),
+        ]
     }
 }
 
@@ -140,7 +138,15 @@ struct Player {
     facing: Direction,
     is_moving: bool,
     map_bounds: Rect,
+    // part of the map whose colliders are loaded, paths can't leave it
+    path_bounds: Rect,
     target_position: Option<Vec2>,
+    // waypoints to `target_position`, next first
+    path: VecDeque<Vec2>,
+    // last click the player can't get to, marked with a cross
+    rejected_target: Option<Vec2>,
+    // walked into something the path didn't know about last frame
+    stuck: bool,
     target_effect_timer: f32,
     wave_active: bool,
     collider: Actor,
@@ -156,7 +162,7 @@ enum Direction {
 }
 
 impl Player {
-    async fn new(world: &mut World) -> Self {
+    async fn new(position: Vec2, world: &mut World) -> Self {
         let texture = load_texture("assets/BasicCharacterSpritesheet.png")
             .await
             .unwrap();
@@ -164,8 +170,8 @@ impl Player {
 
         // Create player collider: collision check minimize at 16px
         println!("{}, {}", screen_width(), screen_height());
-        let position = Vec2::new(792.0, 520.0);
-        let collider = world.add_actor(position, 16, 16);
+        let collider = world.add_actor(position - COLLIDER_OFFSET, 16, 16);
+        world.set_actor_mask(collider, CollisionLayers::SOLID | CollisionLayers::WATER);
         Self {
             position,
             texture,
@@ -174,7 +180,11 @@ impl Player {
             facing: Direction::Down,
             is_moving: false,
             map_bounds: Rect::new(0.0, 0.0, 0.0, 0.0),
+            path_bounds: Rect::new(0.0, 0.0, 0.0, 0.0),
             target_position: None,
+            path: VecDeque::new(),
+            rejected_target: None,
+            stuck: false,
             target_effect_timer: 0.0,
             wave_active: false,
             collider,
@@ -182,83 +192,109 @@ impl Player {
         }
     }
 
+    fn teleport(&mut self, position: Vec2, world: &mut World) {
+        self.position = position;
+        self.target_position = None;
+        self.path.clear();
+        self.rejected_target = None;
+        world.set_actor_position(self.collider, position - COLLIDER_OFFSET);
+    }
+
+    // Unit vector the player walks in, zero when standing still
+    fn heading(&self) -> Vec2 {
+        if !self.is_moving {
+            return Vec2::ZERO;
+        }
+        match self.facing {
+            Direction::Down => vec2(0.0, 1.0),
+            Direction::Up => vec2(0.0, -1.0),
+            Direction::Left => vec2(-1.0, 0.0),
+            Direction::Right => vec2(1.0, 0.0),
+        }
+    }
+
     fn set_map_bounds(&mut self, bounds: Rect) {
         self.map_bounds = bounds;
     }
 
-    fn clamp_position(&mut self) {
-        // Account for character sprite size when clamping
-        let half_sprite = SPRITE_SIZE / 2.0;
-
-        self.position.x = self.position.x.clamp(
-            self.map_bounds.x + half_sprite,
-            self.map_bounds.x + self.map_bounds.w - half_sprite,
-        );
+    fn set_path_bounds(&mut self, bounds: Rect) {
+        self.path_bounds = bounds;
+    }
 
-        self.position.y = self.position.y.clamp(
-            self.map_bounds.y + half_sprite,
-            self.map_bounds.y + self.map_bounds.h - half_sprite,
-        );
+    fn clamp_position(&mut self) {
+        // Keep the whole collider inside the bounds, `max` first so a too
+        // small area pins it to the top left instead of panicking
+        let min = self.map_bounds.point() + COLLIDER_OFFSET;
+        let max = self.map_bounds.point() + self.map_bounds.size() - COLLIDER_OFFSET;
+        self.position = self.position.max(min).min(max);
     }
 
-    fn update(&mut self, dt: f32, world: &mut World, camera: &GameCamera) {
+    fn update(&mut self, dt: f32, world: &mut World, camera: &GameCamera, input: &Input) {
         let speed = 200.0;
-        // Toggle dialog with S key
-        if is_key_pressed(KeyCode::S) {
+        // Toggle dialog
+        if input.is_pressed(Action::ToggleBalance) {
             self.show_dialog = !self.show_dialog;
         }
         let mut movement = Vec2::ZERO;
         self.is_moving = false;
 
         // Handle mouse events
-        if is_mouse_button_pressed(MouseButton::Left) {
-            let screen_position = Vec2::new(mouse_position().0, mouse_position().1);
-            let world_position = camera.screen_to_world(screen_position);
-
-            // Check if click is within map bounds
-            if self.map_bounds.contains(world_position) {
-                self.target_position = Some(world_position);
-                self.target_effect_timer = 0.0;
-                self.wave_active = true;
-            }
+        if input.is_pressed(Action::MoveTo) {
+            let world_position = camera.screen_to_world(input.pointer);
+
+            self.target_position = Some(world_position);
+            self.rejected_target = None;
+            self.target_effect_timer = 0.0;
+            self.wave_active = true;
+            self.plan(world);
         }
 
         // Handle keyboard input
-        if is_key_down(KeyCode::Right) {
+        if input.is_down(Action::MoveRight) {
             movement.x += 1.0;
             self.facing = Direction::Right;
             self.is_moving = true;
-            self.target_position = None; // Cancel mouse movement when using keyboard
+            self.target_position = None; // Cancel mouse movement when using the movement keys
         }
-        if is_key_down(KeyCode::Left) {
+        if input.is_down(Action::MoveLeft) {
             movement.x -= 1.0;
             self.facing = Direction::Left;
             self.is_moving = true;
             self.target_position = None;
         }
-        if is_key_down(KeyCode::Up) {
+        if input.is_down(Action::MoveUp) {
             movement.y -= 1.0;
             self.facing = Direction::Up;
             self.is_moving = true;
             self.target_position = None;
         }
-        if is_key_down(KeyCode::Down) {
+        if input.is_down(Action::MoveDown) {
             movement.y += 1.0;
             self.facing = Direction::Down;
             self.is_moving = true;
             self.target_position = None;
         }
 
-        // Handle mouse movement if we have a target position
-        if let Some(target) = self.target_position {
-            let to_target = target - self.position;
-            let distance = to_target.length();
-
-            // If we're close enough to the target, stop moving
-            if distance < 2.0 {
-                self.target_position = None;
+        // Handle mouse movement along the path to the target position
+        let mut step = speed * dt;
+        if self.target_position.is_none() {
+            self.path.clear();
+        }
+        while let Some(&waypoint) = self.path.front() {
+            // If we're close enough to the waypoint, head for the next one
+            if waypoint.distance(self.position) < 1.0 {
+                self.path.pop_front();
             } else {
-                movement = to_target.normalize();
+                break;
+            }
+        }
+        match self.path.front() {
+            None => self.target_position = None,
+            Some(&waypoint) => {
+                let to_waypoint = waypoint - self.position;
+                // Stop on the waypoint instead of overshooting it
+                step = step.min(to_waypoint.length());
+                movement = to_waypoint.normalize();
                 self.is_moving = true;
 
                 // Update facing direction based on movement
@@ -276,24 +312,25 @@ impl Player {
             }
         }
 
-        // Apply movement with collision detection
+        // Apply movement with collision detection, sliding along walls
         if movement.length() > 0.0 {
             movement = movement.normalize();
-            let desired_position = self.position + movement * speed * dt;
-
-            // Update collider position
-            world.set_actor_position(self.collider, desired_position);
-
-            // If no collision occurred, update player position
-            let half_tile = 8.;
-            if !world.collide_check(
-                self.collider,
-                desired_position + vec2(-half_tile, -half_tile),
-            ) {
-                self.position = desired_position;
+            let before = self.position;
+            let result = world.move_actor(self.collider, movement * step);
+            self.position = world.actor_pos(self.collider) + COLLIDER_OFFSET;
+
+            // Plan again once if something new is in the way, then give up
+            if !self.path.is_empty() && result.blocked() && self.position == before {
+                if self.stuck {
+                    if let Some(target) = self.target_position.take() {
+                        self.reject(target);
+                    }
+                } else {
+                    self.plan(world);
+                }
+                self.stuck = true;
             } else {
-                // Reset collider position if collision occurred
-                world.set_actor_position(self.collider, self.position);
+                self.stuck = false;
             }
         }
 
@@ -308,15 +345,57 @@ impl Player {
             self.animation_frame = 0;
         }
 
+        let unclamped = self.position;
         self.clamp_position();
+        if self.position != unclamped {
+            world.set_actor_position(self.collider, self.position - COLLIDER_OFFSET);
+        }
     }
 
-    fn draw_player(&self, camera: &GameCamera, dialog_texture: &Texture2D) {
-        let player_screen_pos = camera.world_to_screen(self.position);
+    // Finds a way to the target position, or drops it and marks it
+    // unreachable
+    fn plan(&mut self, world: &World) {
+        let Some(target) = self.target_position else {
+            return;
+        };
+        // Walls of unloaded chunks aren't in the world, paths through them
+        // would go through walls
+        let path = self
+            .map_bounds
+            .intersect(self.path_bounds)
+            .and_then(|bounds| world.find_path(self.collider, target - COLLIDER_OFFSET, bounds));
+        match path {
+            Some(path) => {
+                self.path = path
+                    .into_iter()
+                    .map(|waypoint| waypoint + COLLIDER_OFFSET)
+                    .collect();
+            }
+            None => {
+                self.target_position = None;
+                self.reject(target);
+            }
+        }
+    }
+
+    fn reject(&mut self, target: Vec2) {
+        self.path.clear();
+        self.rejected_target = Some(target);
+        self.target_effect_timer = 0.0;
+        self.wave_active = true;
+    }
+
+    // Plans the walk to the target again, for when a door opens or closes
+    fn replan(&mut self, world: &World) {
+        self.stuck = false;
+        self.plan(world);
+    }
+
+    fn draw_player(&self) {
         draw_texture_ex(
             &self.texture,
-            player_screen_pos.x - (SPRITE_SIZE * camera.zoom) / 2.0,
-            player_screen_pos.y - (SPRITE_SIZE * camera.zoom) / 2.0,
+            self.position.x - SPRITE_SIZE / 2.0,
+            self.position.y - SPRITE_SIZE / 2.0,
             WHITE,
             DrawTextureParams {
                 source: Some(Rect::new(
@@ -325,21 +404,26 @@ impl Player {
                     SPRITE_SIZE,
                     SPRITE_SIZE,
                 )),
-                dest_size: Some(Vec2::new(
-                    SPRITE_SIZE * camera.zoom,
-                    SPRITE_SIZE * camera.zoom,
-                )),
+                dest_size: Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE)),
                 ..Default::default()
             },
         );
+    }
+
+    // Base of the sprite, where it touches the ground, for depth sorting
+    fn base_y(&self) -> f32 {
+        self.position.y + COLLIDER_OFFSET.y
+    }
 
+    fn draw_dialog(&self, camera: &GameCamera, dialog_texture: &Texture2D) {
         // Draw dialog box if active
         if self.show_dialog {
-            let player_screen_pos = camera.world_to_screen(self.position);
+            // Fixed size on screen, next to the top of the sprite
+            let head = camera.world_to_screen(self.position - vec2(0.0, SPRITE_SIZE / 2.0));
             let dialog_width = 120.0;
             let dialog_height = 40.0;
-            let dialog_x = player_screen_pos.x - dialog_width / 2.0 + 75.0;
-            let dialog_y = player_screen_pos.y - SPRITE_SIZE * camera.zoom - dialog_height + 100.0;
+            let dialog_x = head.x - dialog_width / 2.0 + 75.0;
+            let dialog_y = head.y;
 
             // Draw dialog box background
             draw_texture_ex(
@@ -364,35 +448,48 @@ impl Player {
         }
     }
 
-    fn draw_wave_effect(&mut self, camera: &GameCamera) {
-        if let Some(target) = self.target_position {
+    fn draw_wave_effect(&mut self) {
+        if let Some(target) = self.target_position.or(self.rejected_target) {
             if self.wave_active {
                 // Update wave timer
                 self.target_effect_timer += get_frame_time() * 2.0;
 
                 // Create single splash effect
                 let wave_time = self.target_effect_timer;
-                let size = 15.0 * wave_time; // Grow from 0 to 20
+                let size = 6.0 * wave_time; // Grow from 0 to 6 world pixels
                 let alpha = 0.8 * (1.0 - wave_time); // Fade out as it grows
 
                 // Only draw if alpha is still visible
-                if alpha > 0.0 {
-                    let target_screen_pos = camera.world_to_screen(target);
+                if alpha > 0.0 && self.rejected_target.is_some() {
+                    // Cross out targets the player can't get to
+                    let color = Color::new(0.9, 0.2, 0.2, alpha);
+                    let arm = 4.0;
+                    for side in [-arm, arm] {
+                        draw_line(
+                            target.x - arm,
+                            target.y + side,
+                            target.x + arm,
+                            target.y - side,
+                            1.0,
+                            color,
+                        );
+                    }
+                } else if alpha > 0.0 {
                     draw_circle_lines(
-                        target_screen_pos.x,
-                        target_screen_pos.y,
+                        target.x,
+                        target.y,
                         size,
-                        2.0, // line thickness
+                        0.8, // line thickness
                         Color::new(1.0, 1.0, 1.0, alpha),
                     );
 
                     // Inner wave
                     let inner_size = size * 0.5;
                     draw_circle_lines(
-                        target_screen_pos.x,
-                        target_screen_pos.y,
+                        target.x,
+                        target.y,
                         inner_size,
-                        1.5, // slightly thinner
+                        0.6, // slightly thinner
                         Color::new(1.0, 1.0, 1.0, alpha),
                     );
                 } else {
@@ -406,9 +503,10 @@ impl Player {
 fn window_conf() -> Conf {
     Conf {
         window_title: "Autonomous World".to_owned(),
-        fullscreen: true,
-        //window_height: 500,
-        //window_width: 500,
+        window_width: 1280,
+        window_height: 720,
+        // Draw at the screen's own resolution, see `GameCamera::pixel_scale`
+        high_dpi: true,
         ..Default::default()
     }
 }
@@ -423,62 +521,27 @@ async fn main() -> Result<Resources, macroquad::Error> {
     // Initialize collision world
     let mut world = World::new();
 
-    let mut player = Player::new(&mut world).await;
-    let mut door = Door::new(Vec2::new(784.0, 560.0), 6).await;
-
-    let mut camera = GameCamera::new();
-
     // Load the map
-    let tiled_map = tiled::load_map(
-        &resources.tiled_map_json,
-        &[
-            ("Grass.png", resources.grass_texture),
-            ("Hills.png", resources.hills_texture),
-            ("Water.png", resources.water_texture),
-            ("WoodenHouseWall.png", resources.wooden_house_wall_texture),
-            ("DoorAnimation.png", resources.door_animation_texture),
-            ("BasicFurniture.png", resources.basic_furniture_texture),
-            ("Plants.png", resources.plants_texture),
-            ("TilledDirt.png", resources.tilled_dirt_texture),
-            ("FarmingPlants.png", resources.farming_plants_texture),
-        ],
-        &[],
-    )
-    .unwrap();
-
-    // Calculate the bounds for the land area (32x32 tiles in center)
-    let tile_size = 16.0;
-    let total_tiles = 90; // total map size in tiles
-    let land_tiles = 32; // land area size in tiles
-
-    // Calculate the offset to center the land area
-    let offset = (total_tiles - land_tiles) as f32 * tile_size / 2.0;
-    // Define the map bounds for just the land area
-    let map_bounds = Rect::new(
-        offset,                        // x start
-        offset,                        // y start
-        land_tiles as f32 * tile_size, // width (32 tiles * 16 pixels)
-        land_tiles as f32 * tile_size, // height (32 tiles * 16 pixels)
-    );
-
-    // Set player's map bounds
-    player.set_map_bounds(map_bounds);
-
-    // Create colliders for house tiles
-    let map_width_tiles = tiled_map.raw_tiled_map.width as usize;
-    let map_height_tiles = tiled_map.raw_tiled_map.height as usize;
-    let mut static_colliders = vec![Tile::Empty; map_width_tiles * map_height_tiles];
-
-    // Set up colliders for house tiles
-    for (x, y, tile) in tiled_map.tiles("House", None) {
-        if tile.is_some() {
-            let index = (y as usize) * map_width_tiles + (x as usize);
-            static_colliders[index] = Tile::Solid;
-        }
-    }
-
-    // Add the static colliders to the world
-    world.add_static_tiled_layer(static_colliders, 16.0, 16.0, map_width_tiles, 1);
+    let tilesets = resources.tilesets();
+    let mut scene = Scene::load(START_MAP, &tilesets).await.unwrap();
+    scene.enter(&mut world).await;
+    let mut transition: Option<Transition> = None;
+    // Maps the player has been on, only maps walked into pan
+    let mut visited = HashSet::from([scene.name.clone()]);
+    // Paid transactions flash the screen once they go through
+    let mut was_paid = get_state().is_paid;
+
+    let spawn = scene
+        .data
+        .spawn("player")
+        .expect("start map has no player spawn");
+    let mut player = Player::new(spawn, &mut world).await;
+    let mut camera = GameCamera::new(vec2(screen_width(), screen_height()));
+    camera.set_bounds(scene.data.rect());
+    camera.snap_to(player.position);
+
+    // Keep the player on the playable part of the map
+    player.set_map_bounds(scene.data.bounds());
 
     // UI
     let window_style = root_ui()
@@ -525,16 +588,28 @@ async fn main() -> Result<Resources, macroquad::Error> {
     };
     root_ui().push_skin(&ui_skin);
 
-    let window_size = vec2(370.0, 320.0);
+    let window_size = vec2(370.0, 420.0);
 
     emit_game_start();
 
     let mut bg_animation = resources.bg_animation;
 
+    // Bindings saved by the page, handed back with the defaults filled in
+    let mut input_map = InputMap::load();
+    input_map.save();
+    let mut controls_menu = ControlsMenu::default();
+    let mut touch_controls = TouchControls::default();
+    let mut devices = Devices::default();
+
     loop {
         clear_background(WHITE);
 
         let dt = get_frame_time();
+        // Windows can be resized and phones turned in any state
+        camera.update_viewport_size();
+        input_map.reload_if_changed();
+        devices.update();
+        let mut input = input_map.poll(&devices);
 
         match game_state {
             GameState::MainMenu => {
@@ -578,51 +653,144 @@ async fn main() -> Result<Resources, macroquad::Error> {
                                 request_disconnect();
                             }
                         };
+                        if ui.button(vec2(14.0, 235.0), "Controls") {
+                            game_state = GameState::Controls;
+                        }
                     },
                 );
             }
+            GameState::Controls => {
+                if controls_menu.update(&mut input_map) {
+                    game_state = GameState::MainMenu;
+                }
+                controls_menu.draw(&input_map);
+            }
             GameState::Playing => {
-                camera.update_viewport_size();
-
-                // Update player with collision world
-                player.update(dt, &mut world, &camera);
-
-                // Update door animation
-                // Toggle door when space is pressed and player is near
-                let door_position = door.get_position();
-                let distance_to_door = (player.position - door_position).length();
-                let interaction_distance = 16.0; // Adjust this value to change interaction range
-
-                let playtable_position = Vec2::new(736.0, 544.0);
-                let distance_to_playtable = (player.position - playtable_position).length();
-                if is_key_pressed(KeyCode::Space) {
-                    if !door.is_animating() && distance_to_door < interaction_distance {
-                        door.toggle(&mut world);
+                // Load the map chunks around the camera before anything collides
+                let loaded = scene.stream(&mut world, camera.drawn_view());
+                player.set_path_bounds(loaded);
+                touch_controls.update(&mut input);
+
+                // Input is ignored while the screen fades
+                if transition.is_none() {
+                    camera.handle_zoom_input(&input);
+                    // Update player with collision world
+                    player.update(dt, &mut world, &camera, &input);
+                }
+
+                let events: Vec<TriggerEvent> = world
+                    .update_triggers()
+                    .into_iter()
+                    .filter(|event| event.actor == player.collider)
+                    .collect();
+                // Interaction zones of `tag` the player is standing in
+                let nearby = |tag: u32| {
+                    events
+                        .iter()
+                        .filter(move |event| {
+                            event.tag == tag && event.kind != TriggerEventKind::Exit
+                        })
+                        .map(|event| event.user_data as usize)
+                };
+
+                // Use doors and interactables when Interact is pressed and player is near
+                if transition.is_none() && input.is_pressed(Action::Interact) {
+                    for index in nearby(DOOR_TRIGGER) {
+                        if !scene.doors[index].is_animating() {
+                            scene.doors[index].toggle(&mut world);
+                            // The way to the clicked target may have opened or closed
+                            player.replan(&world);
+                        }
                     }
-                    if distance_to_playtable < interaction_distance {
-                        request_paid_transaction();
+                    for index in nearby(INTERACT_TRIGGER) {
+                        match scene.interactions[index].as_str() {
+                            "pay" => request_paid_transaction(),
+                            action => warn!("unknown interaction {:?}", action),
+                        }
                     }
                 }
 
-                door.update(dt);
+                for event in &events {
+                    if transition.is_none()
+                        && event.tag == PORTAL_TRIGGER
+                        && event.kind == TriggerEventKind::Enter
+                    {
+                        let (map, spawn) = scene.portals[event.user_data as usize].clone();
+                        transition = Some(Transition::start(map, spawn, &mut camera.effects));
+                    }
+                }
 
-                // Update camera to follow player
-                camera.update(player.position);
+                if let Some(fade) = &mut transition {
+                    // Swap maps and move the player while the screen is black
+                    if fade.update(&mut camera.effects) {
+                        match fade.map.as_deref() {
+                            Some(name) if name != scene.name => {
+                                match Scene::load(name, &tilesets).await {
+                                    Ok(next) => {
+                                        scene.leave(&mut world);
+                                        scene = next;
+                                        scene.enter(&mut world).await;
+                                    }
+                                    Err(err) => warn!("can't load map {:?}: {}", name, err),
+                                }
+                            }
+                            _ => {}
+                        }
+                        match scene.data.spawn(&fade.spawn) {
+                            Some(position) => {
+                                player.teleport(position, &mut world);
+                                player.set_map_bounds(scene.data.bounds());
+                                camera.set_bounds(scene.data.rect());
+                                camera.snap_to(player.position);
+                                if visited.insert(scene.name.clone()) {
+                                    if let Some(point) = scene.pan {
+                                        camera.pan_to(point, PAN_DURATION, PAN_HOLD);
+                                    }
+                                }
+                            }
+                            None => warn!("map {:?} has no spawn {:?}", scene.name, fade.spawn),
+                        }
+                    }
+                    if fade.is_finished(&camera.effects) {
+                        transition = None;
+                    }
+                }
 
-                // Draw layers in order
-                draw_tiled_layer(
-                    &tiled_map,
-                    &camera,
-                    vec!["Ocean", "Land", "Floor", "House", "Furniture"],
-                );
+                for door in &mut scene.doors {
+                    if door.update(dt) {
+                        camera.effects.push(Effect::Shake(DOOR_SLAM));
+                    }
+                }
 
-                // Draw player at center of screen
-                player.draw_player(&camera, &resources.dialog_box_texture);
+                let is_paid = get_state().is_paid;
+                if is_paid && !was_paid {
+                    camera.effects.push(Effect::Flash {
+                        color: PAID_FLASH,
+                        duration: 0.4,
+                    });
+                }
+                was_paid = is_paid;
 
-                door.draw_door(&camera);
+                // Update camera to follow player
+                camera.update(dt, player.position, player.heading());
+
+                // Draw the ground layers in order, then everything that can
+                // be in front of or behind the player
+                let cells = scene.cells_touching(camera.drawn_view());
+                // Animated tiles share one clock so neighbouring tiles stay in step
+                let time = get_time();
+                camera.begin_world();
+                draw_ground(&scene, &cells, time);
+                draw_depth_sorted(&scene, &player, &cells, time);
 
                 // Draw target indicator if exists
-                player.draw_wave_effect(&camera);
+                player.draw_wave_effect();
+
+                camera.begin_hud();
+                player.draw_dialog(&camera, &resources.dialog_box_texture);
+
+                camera.effects.draw();
+                touch_controls.draw();
 
                 draw_text(&format!("FPS: {}", get_fps()), 10.0, 20.0, 20.0, BLACK);
             }
@@ -632,25 +800,53 @@ async fn main() -> Result<Resources, macroquad::Error> {
     }
 }
 
-fn draw_tiled_layer(tiled_map: &Map, camera: &GameCamera, layers: Vec<&str>) {
-    // Calculate camera offset for drawing
-    let camera_offset = Vec2::new(
-        screen_width() / 2.0 - camera.position.x * camera.zoom,
-        screen_height() / 2.0 - camera.position.y * camera.zoom,
-    );
-    let map_width = tiled_map.raw_tiled_map.width as f32 * tiled_map.raw_tiled_map.tilewidth as f32;
-    let map_height =
-        tiled_map.raw_tiled_map.height as f32 * tiled_map.raw_tiled_map.tileheight as f32;
-    for layer in layers {
-        tiled_map.draw_tiles(
-            layer,
-            Rect::new(
-                camera_offset.x,
-                camera_offset.y,
-                map_width * camera.zoom,
-                map_height * camera.zoom,
-            ),
-            None,
-        );
+// Columns and rows of cells, see `Scene::cells_touching`
+type Cells = (Range<i32>, Range<i32>);
+
+// Draws the `cells` of each layer that isn't depth sorted, in order
+fn draw_ground(scene: &Scene, cells: &Cells, time: f64) {
+    for (index, layer) in scene.tile_layers().enumerate() {
+        if !layer.ysort() {
+            for tile in scene.tiles(index, cells.clone()) {
+                scene.draw_tile(layer, tile, time);
+            }
+        }
+    }
+}
+
+// Something drawn in the depth sorted pass
+enum Sprite<'a> {
+    Tile {
+        layer: &'a LayerData,
+        tile: &'a PlacedTile,
+    },
+    Player,
+    Door(usize),
+}
+
+// Draws the tiles of `ysort` layers, the player and the doors back to front,
+// by the y of their base
+fn draw_depth_sorted(scene: &Scene, player: &Player, cells: &Cells, time: f64) {
+    let mut sprites = vec![];
+    for (index, layer) in scene.tile_layers().enumerate() {
+        if layer.ysort() {
+            for tile in scene.tiles(index, cells.clone()) {
+                sprites.push((tile.depth, Sprite::Tile { layer, tile }));
+            }
+        }
+    }
+    sprites.push((player.base_y(), Sprite::Player));
+    for (index, door) in scene.doors.iter().enumerate() {
+        sprites.push((door.base_y(), Sprite::Door(index)));
+    }
+    // stable, so tiles stay behind characters standing on the same row
+    sprites.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (_, sprite) in sprites {
+        match sprite {
+            Sprite::Tile { layer, tile } => scene.draw_tile(layer, tile, time),
+            Sprite::Player => player.draw_player(),
+            Sprite::Door(index) => scene.doors[index].draw_door(),
+        }
     }
 }
@@ -0,0 +1,1445 @@
+//! Tiled map data: tile layers cut into chunks, tilesets with their tile
+//! properties, collision shapes and animations, and object layers. Used to
+//! build the collision world, place entities and find what to draw.
+
+use macroquad::math::{vec2, Rect, Vec2};
+use serde::Deserialize;
+
+use macroquad::logging::warn;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::platformer::{CollisionLayers, Tile, TileShape, TiledLayer, World};
+
+// Tiled stores the flip flags in the top bits of a gid
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const GID_MASK: u32 = 0x1fff_ffff;
+
+/// Chunk size in tiles, the same as Tiled's infinite maps
+pub const CHUNK_SIZE: i32 = 16;
+
+// Names `bounds` looks for
+const BOUNDS: &str = "bounds";
+const LAND: &str = "Land";
+// Layer property for tiles drawn in depth order
+const YSORT: &str = "ysort";
+// Tile property of tall objects' upper tiles, the rows down to the object's
+// bottom row
+const BASE: &str = "base";
+
+#[derive(Deserialize, Debug)]
+pub struct MapData {
+    pub width: u32,
+    pub height: u32,
+    pub tilewidth: u32,
+    pub tileheight: u32,
+    /// Tiled's infinite maps keep their layers in chunks, anywhere around
+    /// the origin, and their size is that of the chunks
+    #[serde(default)]
+    pub infinite: bool,
+    pub layers: Vec<LayerData>,
+    #[serde(default)]
+    pub tilesets: Vec<TilesetData>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LayerData {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    // Cells of fixed-size maps, gids in rows of `width`, and of infinite
+    // maps. `from_json` moves both into `chunks`.
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default, rename = "chunks")]
+    tiled_chunks: Vec<ChunkData>,
+    /// Gids by chunk column and row, in `CHUNK_SIZE` rows of `CHUNK_SIZE`
+    /// cells, 0 for an empty cell. Chunks without tiles are left out.
+    #[serde(skip)]
+    pub chunks: HashMap<(i32, i32), Vec<u32>>,
+    #[serde(default)]
+    pub offsetx: f32,
+    #[serde(default)]
+    pub offsety: f32,
+    // only on object layers
+    #[serde(default)]
+    pub objects: Vec<ObjectData>,
+    #[serde(default)]
+    pub properties: Vec<PropertyData>,
+}
+
+// A chunk of an infinite map's layer, in cells
+#[derive(Deserialize, Debug)]
+struct ChunkData {
+    x: i32,
+    y: i32,
+    width: u32,
+    data: Vec<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TilesetData {
+    #[serde(default)]
+    pub name: String,
+    pub firstgid: u32,
+    pub tilewidth: u32,
+    pub tileheight: u32,
+    // file name of the tileset image
+    #[serde(default)]
+    pub image: String,
+    #[serde(default)]
+    pub columns: u32,
+    #[serde(default)]
+    pub tilecount: u32,
+    #[serde(default)]
+    pub margin: u32,
+    #[serde(default)]
+    pub spacing: u32,
+    // only tiles with extra data are listed
+    #[serde(default)]
+    pub tiles: Vec<TileData>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TileData {
+    pub id: u32,
+    #[serde(default)]
+    pub properties: Vec<PropertyData>,
+    // collision shapes drawn in Tiled's tile collision editor
+    #[serde(default)]
+    pub objectgroup: Option<ObjectGroupData>,
+    // frames made in Tiled's tile animation editor
+    #[serde(default)]
+    pub animation: Vec<FrameData>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameData {
+    // id in the same tileset
+    pub tileid: u32,
+    // milliseconds
+    pub duration: u32,
+}
+
+impl TilesetData {
+    /// Where tile `id` is in the tileset image, in pixels.
+    pub fn source(&self, id: u32) -> Rect {
+        let columns = self.columns.max(1);
+        let step = vec2(
+            (self.tilewidth + self.spacing) as f32,
+            (self.tileheight + self.spacing) as f32,
+        );
+        let pos = vec2((id % columns) as f32, (id / columns) as f32) * step + self.margin as f32;
+        Rect::new(pos.x, pos.y, self.tilewidth as f32, self.tileheight as f32)
+    }
+}
+
+/// A tile to draw: its tileset by index in `MapData::tilesets`, its id in
+/// the tileset and the flip flags of its gid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileSprite {
+    pub tileset: usize,
+    pub id: u32,
+    flags: u32,
+}
+
+impl TileSprite {
+    /// Whether the tile is flipped horizontally, vertically and diagonally.
+    /// Tiled flips diagonally first.
+    pub fn flips(&self) -> (bool, bool, bool) {
+        (
+            self.flags & FLIPPED_HORIZONTALLY != 0,
+            self.flags & FLIPPED_VERTICALLY != 0,
+            self.flags & FLIPPED_DIAGONALLY != 0,
+        )
+    }
+}
+
+/// Animated tiles of a map, by tileset name and tile id.
+#[derive(Debug, Default)]
+pub struct TileAnimations {
+    // by tileset first, so drawing a tile looks it up without allocating
+    tiles: HashMap<String, HashMap<u32, Vec<FrameData>>>,
+}
+
+impl TileAnimations {
+    /// Id to draw for tile `id` of `tileset` at `time` seconds on the shared
+    /// animation clock. Tiles without an animation are returned as is.
+    pub fn frame(&self, tileset: &str, id: u32, time: f64) -> u32 {
+        let Some(frames) = self.tiles.get(tileset).and_then(|tiles| tiles.get(&id)) else {
+            return id;
+        };
+        let length: u64 = frames.iter().map(|frame| frame.duration as u64).sum();
+        if length == 0 {
+            return id;
+        }
+
+        let mut elapsed = (time.max(0.0) * 1000.0) as u64 % length;
+        for frame in frames {
+            if elapsed < frame.duration as u64 {
+                return frame.tileid;
+            }
+            elapsed -= frame.duration as u64;
+        }
+        id
+    }
+}
+
+/// Custom property, the value is a string, number or bool depending on its type.
+#[derive(Deserialize, Debug)]
+pub struct PropertyData {
+    pub name: String,
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+/// Value of a tile's `collision` property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    /// Walkable, hides whatever collides on the layers below
+    None,
+    Solid,
+    JumpThrough,
+    /// Blocks walking, in its own category so swimmers and boats can ignore it
+    Water,
+}
+
+impl Collision {
+    pub fn parse(value: &str) -> Option<Collision> {
+        match value {
+            "none" => Some(Collision::None),
+            "solid" => Some(Collision::Solid),
+            "jumpthrough" => Some(Collision::JumpThrough),
+            "water" => Some(Collision::Water),
+            _ => None,
+        }
+    }
+
+    fn tile(self) -> Tile {
+        match self {
+            Collision::None => Tile::Empty,
+            Collision::Solid | Collision::Water => Tile::Solid,
+            Collision::JumpThrough => Tile::JumpThrough,
+        }
+    }
+
+    fn category(self) -> CollisionLayers {
+        match self {
+            Collision::Water => CollisionLayers::WATER,
+            _ => CollisionLayers::SOLID,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ObjectGroupData {
+    #[serde(default)]
+    pub objects: Vec<ObjectData>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ObjectData {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub ty: String,
+    #[serde(default)]
+    pub properties: Vec<PropertyData>,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+    // degrees clockwise around (x, y)
+    #[serde(default)]
+    pub rotation: f32,
+    // relative to (x, y)
+    #[serde(default)]
+    pub polygon: Option<Vec<PointData>>,
+    #[serde(default)]
+    pub point: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct PointData {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Game object placed on an object layer, by Tiled object type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entity {
+    /// Where the player appears, portals refer to spawns by name
+    Spawn {
+        name: String,
+        position: Vec2,
+    },
+    Door {
+        rect: Rect,
+    },
+    /// Sends the player to the spawn called `spawn`, in `map` if set
+    Portal {
+        rect: Rect,
+        map: Option<String>,
+        spawn: String,
+    },
+    /// Runs `action` when the player interacts inside `rect`
+    Interact {
+        rect: Rect,
+        action: String,
+    },
+    /// What the camera shows the first time the player walks into the map
+    Pan {
+        position: Vec2,
+    },
+}
+
+impl ObjectData {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+
+    /// String property, `None` if missing or of another type.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .and_then(|property| property.value.as_str())
+    }
+
+    /// The entity this object describes, `None` for unknown types or missing
+    /// properties. Positions are relative to the object layer.
+    pub fn entity(&self) -> Option<Entity> {
+        let entity = match self.ty.as_str() {
+            // points have no size, spawns drawn as rects use their center
+            "spawn" => Entity::Spawn {
+                name: self.name.clone(),
+                position: self.rect().center(),
+            },
+            "door" => Entity::Door { rect: self.rect() },
+            "portal" => Entity::Portal {
+                rect: self.rect(),
+                map: self.property("map").map(str::to_owned),
+                spawn: self.property("spawn")?.to_owned(),
+            },
+            "interact" => Entity::Interact {
+                rect: self.rect(),
+                action: self.property("action")?.to_owned(),
+            },
+            "pan" => Entity::Pan {
+                position: self.rect().center(),
+            },
+            _ => return None,
+        };
+        Some(entity)
+    }
+
+    /// Collision shape of the object, `None` for points. Ellipses use their
+    /// bounding box.
+    pub fn tile_shape(&self) -> Option<TileShape> {
+        if self.point {
+            return None;
+        }
+
+        let origin = vec2(self.x, self.y);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let place = |p: Vec2| origin + vec2(p.x * cos - p.y * sin, p.x * sin + p.y * cos);
+        match &self.polygon {
+            Some(points) => Some(TileShape::Polygon(
+                points.iter().map(|p| place(vec2(p.x, p.y))).collect(),
+            )),
+            None if self.rotation == 0.0 => Some(TileShape::Rect(Rect::new(
+                self.x,
+                self.y,
+                self.width,
+                self.height,
+            ))),
+            None => Some(TileShape::Polygon(vec![
+                place(vec2(0.0, 0.0)),
+                place(vec2(self.width, 0.0)),
+                place(vec2(self.width, self.height)),
+                place(vec2(0.0, self.height)),
+            ])),
+        }
+    }
+}
+
+impl LayerData {
+    pub fn offset(&self) -> Vec2 {
+        vec2(self.offsetx, self.offsety)
+    }
+
+    /// Whether the layer's tiles are drawn in depth order with the
+    /// characters, from its `ysort` bool property.
+    pub fn ysort(&self) -> bool {
+        self.properties
+            .iter()
+            .find(|property| property.name == YSORT)
+            .and_then(|property| property.value.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Tile id including the flip flags, 0 for empty cells.
+    pub fn raw_gid(&self, x: i32, y: i32) -> u32 {
+        let (chunk, index) = chunk_of(x, y);
+        self.chunks.get(&chunk).map_or(0, |cells| cells[index])
+    }
+
+    /// Cells of a chunk that have a tile, with their raw gids, row by row.
+    pub fn chunk_tiles(&self, (x, y): (i32, i32)) -> impl Iterator<Item = (i32, i32, u32)> + '_ {
+        let cells = self.chunks.get(&(x, y)).map_or(&[][..], Vec::as_slice);
+        cells
+            .iter()
+            .enumerate()
+            .filter(|(_, &raw_gid)| raw_gid != 0)
+            .map(move |(index, &raw_gid)| {
+                let index = index as i32;
+                (
+                    x * CHUNK_SIZE + index % CHUNK_SIZE,
+                    y * CHUNK_SIZE + index / CHUNK_SIZE,
+                    raw_gid,
+                )
+            })
+    }
+
+    /// Every cell that has a tile, chunk by chunk in no particular order.
+    pub fn tiles(&self) -> impl Iterator<Item = (i32, i32, u32)> + '_ {
+        self.chunks
+            .keys()
+            .flat_map(|&chunk| self.chunk_tiles(chunk))
+    }
+
+    // Moves the cells of `data` or of Tiled's chunks into `chunks`
+    fn cut_into_chunks(&mut self) {
+        let width = (self.width as usize).max(1);
+        let data = std::mem::take(&mut self.data);
+        let rows = data.chunks(width).enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, &raw_gid)| (x as i32, y as i32, raw_gid))
+        });
+        let chunks =
+            std::mem::take(&mut self.tiled_chunks)
+                .into_iter()
+                .flat_map(|chunk| {
+                    let width = chunk.width.max(1) as i32;
+                    chunk.data.into_iter().zip(0..).map(move |(raw_gid, i)| {
+                        (chunk.x + i % width, chunk.y + i / width, raw_gid)
+                    })
+                });
+        for (x, y, raw_gid) in rows.chain(chunks) {
+            if raw_gid == 0 {
+                continue;
+            }
+            let (chunk, index) = chunk_of(x, y);
+            self.chunks
+                .entry(chunk)
+                .or_insert_with(|| vec![0; (CHUNK_SIZE * CHUNK_SIZE) as usize])[index] = raw_gid;
+        }
+    }
+}
+
+// Chunk column and row of cell (`x`, `y`), and the cell's index in the chunk
+fn chunk_of(x: i32, y: i32) -> ((i32, i32), usize) {
+    let chunk = (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE));
+    let index = y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + x.rem_euclid(CHUNK_SIZE);
+    (chunk, index as usize)
+}
+
+impl MapData {
+    pub fn from_json(json: &str) -> Result<MapData, serde_json::Error> {
+        let mut map: MapData = serde_json::from_str(json)?;
+        for layer in &mut map.layers {
+            layer.cut_into_chunks();
+        }
+        Ok(map)
+    }
+
+    /// Entities of every object layer, in world coordinates. Objects that
+    /// aren't entities are skipped with a warning.
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut entities = vec![];
+        for layer in self.layers.iter().filter(|layer| layer.ty == "objectgroup") {
+            let offset = layer.offset();
+            for object in &layer.objects {
+                if object.name == BOUNDS {
+                    continue;
+                }
+                let Some(mut entity) = object.entity() else {
+                    warn!("skipping {:?} object {:?}", object.ty, object.name);
+                    continue;
+                };
+                match &mut entity {
+                    Entity::Spawn { position, .. } | Entity::Pan { position } => {
+                        *position += offset
+                    }
+                    Entity::Door { rect }
+                    | Entity::Portal { rect, .. }
+                    | Entity::Interact { rect, .. } => {
+                        *rect = rect.offset(offset);
+                    }
+                }
+                entities.push(entity);
+            }
+        }
+        entities
+    }
+
+    /// Playable area: the object named "bounds" if there is one, otherwise
+    /// the cells the "Land" layer covers, otherwise the whole map.
+    pub fn bounds(&self) -> Rect {
+        let object_layers = self.layers.iter().filter(|layer| layer.ty == "objectgroup");
+        for layer in object_layers {
+            if let Some(object) = layer.objects.iter().find(|object| object.name == BOUNDS) {
+                return object.rect().offset(layer.offset());
+            }
+        }
+
+        let tile_size = vec2(self.tilewidth as f32, self.tileheight as f32);
+        let land = self
+            .layers
+            .iter()
+            .find(|layer| layer.name == LAND && layer.ty == "tilelayer");
+        if let Some(land) = land {
+            let cells = land.tiles().map(|(x, y, _)| (x, y));
+            let extent = cells.fold(None, |extent: Option<(i32, i32, i32, i32)>, (x, y)| {
+                Some(match extent {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    None => (x, y, x, y),
+                })
+            });
+            if let Some((x0, y0, x1, y1)) = extent {
+                let min = vec2(x0 as f32, y0 as f32) * tile_size;
+                let size = vec2((x1 - x0 + 1) as f32, (y1 - y0 + 1) as f32) * tile_size;
+                return Rect::new(min.x, min.y, size.x, size.y).offset(land.offset());
+            }
+        }
+
+        self.rect()
+    }
+
+    /// The whole map, in world pixels. Infinite maps cover their chunks.
+    pub fn rect(&self) -> Rect {
+        let tile_size = vec2(self.tilewidth as f32, self.tileheight as f32);
+        if self.infinite {
+            let (columns, rows) = self.chunk_extent();
+            let chunk = tile_size * CHUNK_SIZE as f32;
+            let min = vec2(columns.start as f32, rows.start as f32) * chunk;
+            let size = vec2(columns.len() as f32, rows.len() as f32) * chunk;
+            return Rect::new(min.x, min.y, size.x, size.y);
+        }
+        let size = vec2(self.width as f32, self.height as f32) * tile_size;
+        Rect::new(0.0, 0.0, size.x, size.y)
+    }
+
+    /// Columns and rows of the chunks that have tiles on any layer.
+    pub fn chunk_extent(&self) -> (Range<i32>, Range<i32>) {
+        let chunks = self.layers.iter().flat_map(|layer| layer.chunks.keys());
+        let extent = chunks.fold(None, |extent: Option<(i32, i32, i32, i32)>, &(x, y)| {
+            Some(match extent {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            })
+        });
+        match extent {
+            Some((x0, y0, x1, y1)) => (x0..x1 + 1, y0..y1 + 1),
+            None => (0..0, 0..0),
+        }
+    }
+
+    pub fn spawn(&self, name: &str) -> Option<Vec2> {
+        self.entities().into_iter().find_map(|entity| match entity {
+            Entity::Spawn {
+                name: spawn,
+                position,
+            } if spawn == name => Some(position),
+            _ => None,
+        })
+    }
+
+    /// Tileset entry of a tile, `None` if its tileset has no extra data for it.
+    fn tile_data(&self, gid: u32) -> Option<(&TilesetData, &TileData)> {
+        let gid = gid & GID_MASK;
+        let tileset = self
+            .tilesets
+            .iter()
+            .filter(|tileset| tileset.firstgid <= gid)
+            .max_by_key(|tileset| tileset.firstgid)?;
+        let tile = tileset
+            .tiles
+            .iter()
+            .find(|tile| tile.id == gid - tileset.firstgid)?;
+        Some((tileset, tile))
+    }
+
+    pub fn tile_animations(&self) -> TileAnimations {
+        let mut tiles: HashMap<String, HashMap<u32, Vec<FrameData>>> = HashMap::new();
+        for tileset in &self.tilesets {
+            for tile in tileset
+                .tiles
+                .iter()
+                .filter(|tile| !tile.animation.is_empty())
+            {
+                tiles
+                    .entry(tileset.name.clone())
+                    .or_default()
+                    .insert(tile.id, tile.animation.clone());
+            }
+        }
+        TileAnimations { tiles }
+    }
+
+    /// World y of the bottom of the object tile (`x`, `y`) of `layer` is part
+    /// of, to draw it in depth order. The upper tiles of objects taller than
+    /// a tile have a `base` property, how many rows the object goes down.
+    pub fn depth(&self, layer: &LayerData, x: i32, y: i32) -> f32 {
+        let rows = self
+            .tile_property(layer.raw_gid(x, y), BASE)
+            .and_then(|value| value.as_u64())
+            .unwrap_or(0);
+        (y as f32 + 1.0 + rows as f32) * self.tileheight as f32 + layer.offsety
+    }
+
+    /// What to draw for a tile, `None` for empty cells and gids of no
+    /// tileset.
+    pub fn tile_sprite(&self, raw_gid: u32) -> Option<TileSprite> {
+        let gid = raw_gid & GID_MASK;
+        let (tileset, data) = self
+            .tilesets
+            .iter()
+            .enumerate()
+            .filter(|(_, tileset)| tileset.firstgid <= gid)
+            .max_by_key(|(_, tileset)| tileset.firstgid)?;
+        let id = gid - data.firstgid;
+        // tilesets without a count are taken at their word
+        (gid != 0 && (data.tilecount == 0 || id < data.tilecount)).then_some(TileSprite {
+            tileset,
+            id,
+            flags: raw_gid & !GID_MASK,
+        })
+    }
+
+    pub fn tile_property(&self, gid: u32, name: &str) -> Option<&serde_json::Value> {
+        let (_, tile) = self.tile_data(gid)?;
+        tile.properties
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| &property.value)
+    }
+
+    /// The tile's `collision` property, `None` if it has none or an unknown value.
+    pub fn tile_collision(&self, gid: u32) -> Option<Collision> {
+        let value = self.tile_property(gid, "collision")?;
+        let collision = value.as_str().and_then(Collision::parse);
+        if collision.is_none() {
+            warn!("tile {} has unknown collision {}", gid & GID_MASK, value);
+        }
+        collision
+    }
+
+    /// Collision shapes of a tile from its tileset, flipped like `raw_gid`,
+    /// relative to its cell. Tiled draws tiles of another size than the grid
+    /// from the bottom left corner of their cell, so a tall tile's shapes can
+    /// stick out of the top. Empty if the tile has none and blocks its whole
+    /// cell.
+    pub fn tile_shapes(&self, raw_gid: u32) -> Vec<TileShape> {
+        let Some((tileset, tile)) = self.tile_data(raw_gid) else {
+            return vec![];
+        };
+        let Some(objectgroup) = &tile.objectgroup else {
+            return vec![];
+        };
+
+        let size = vec2(tileset.tilewidth as f32, tileset.tileheight as f32);
+        let anchor = vec2(0.0, self.tileheight as f32 - size.y);
+        // flipped within the tile, then placed on the cell
+        let place = |p: Vec2| {
+            let mut p = p;
+            if raw_gid & FLIPPED_DIAGONALLY != 0 {
+                p = vec2(p.y, p.x);
+            }
+            if raw_gid & FLIPPED_HORIZONTALLY != 0 {
+                p.x = size.x - p.x;
+            }
+            if raw_gid & FLIPPED_VERTICALLY != 0 {
+                p.y = size.y - p.y;
+            }
+            anchor + p
+        };
+        objectgroup
+            .objects
+            .iter()
+            .filter_map(ObjectData::tile_shape)
+            .map(|shape| match shape {
+                TileShape::Rect(rect) => {
+                    let a = place(rect.point());
+                    let b = place(rect.point() + rect.size());
+                    let min = a.min(b);
+                    TileShape::Rect(Rect::new(
+                        min.x,
+                        min.y,
+                        (a.x - b.x).abs(),
+                        (a.y - b.y).abs(),
+                    ))
+                }
+                TileShape::Polygon(points) => {
+                    TileShape::Polygon(points.into_iter().map(place).collect())
+                }
+            })
+            .collect()
+    }
+
+    /// Adds static colliders for every tile layer, from the tiles' `collision`
+    /// property. A tile with the property overrides the tiles below it on
+    /// layers with the same offset, so `collision=none` land can cover water.
+    /// Tiles with collision shapes in their tileset only block those.
+    /// The game streams chunks with `add_collision_region` instead.
+    #[cfg(test)]
+    pub fn add_collision_layers(&self, world: &mut World) -> Vec<TiledLayer> {
+        let (columns, rows) = self.chunk_extent();
+        self.add_collision_region(
+            world,
+            columns.start * CHUNK_SIZE,
+            rows.start * CHUNK_SIZE,
+            columns.len() as i32 * CHUNK_SIZE,
+            rows.len() as i32 * CHUNK_SIZE,
+        )
+    }
+
+    /// Same as `add_collision_layers`, for the `width`x`height` cells whose
+    /// top left cell is (`x`, `y`), so a large map can be loaded a chunk at a
+    /// time. Returns the added layers, to remove them when the chunk unloads.
+    pub fn add_collision_region(
+        &self,
+        world: &mut World,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Vec<TiledLayer> {
+        let (x0, y0) = (x, y);
+        let mut added = vec![];
+        // cells decided by a layer above, per layer offset
+        let mut decided: HashMap<(u32, u32), HashSet<(i32, i32)>> = HashMap::new();
+        let mut shapes = HashMap::new();
+
+        // Tiled lists layers bottom to top
+        for layer in self.layers.iter().rev() {
+            if layer.ty != "tilelayer" {
+                continue;
+            }
+            let decided = decided
+                .entry((layer.offsetx.to_bits(), layer.offsety.to_bits()))
+                .or_default();
+
+            let cells = (width * height) as usize;
+            let mut categories: Vec<(CollisionLayers, Vec<Tile>)> = vec![];
+            for y in y0..y0 + height {
+                for x in x0..x0 + width {
+                    let raw_gid = layer.raw_gid(x, y);
+                    if raw_gid == 0 || decided.contains(&(x, y)) {
+                        continue;
+                    }
+                    let Some(collision) = self.tile_collision(raw_gid) else {
+                        continue;
+                    };
+                    decided.insert((x, y));
+                    if collision == Collision::None {
+                        continue;
+                    }
+
+                    let category = collision.category();
+                    let index = match categories.iter().position(|(c, _)| *c == category) {
+                        Some(index) => index,
+                        None => {
+                            categories.push((category, vec![Tile::Empty; cells]));
+                            categories.len() - 1
+                        }
+                    };
+                    categories[index].1[((y - y0) * width + x - x0) as usize] = collision.tile();
+                }
+            }
+
+            let tile_size = vec2(self.tilewidth as f32, self.tileheight as f32);
+            for (category, static_colliders) in categories {
+                let colliding: Vec<usize> = (0..cells)
+                    .filter(|&cell| static_colliders[cell] != Tile::Empty)
+                    .collect();
+                let static_layer = world.add_static_tiled_layer_at(
+                    layer.offset() + vec2(x0 as f32, y0 as f32) * tile_size,
+                    static_colliders,
+                    tile_size.x,
+                    tile_size.y,
+                    width as usize,
+                    category,
+                );
+                for cell in colliding {
+                    let (x, y) = (cell as i32 % width, cell as i32 / width);
+                    let raw_gid = layer.raw_gid(x0 + x, y0 + y);
+                    let shapes = shapes
+                        .entry(raw_gid)
+                        .or_insert_with(|| self.tile_shapes(raw_gid));
+                    if !shapes.is_empty() {
+                        world.set_tile_shapes(static_layer, x as usize, y as usize, shapes);
+                    }
+                }
+                added.push(static_layer);
+            }
+        }
+        added
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2 map of 16x32 tiles, the wall layer shifted by (8, -4) like Tiled's
+    // layer offset. The second gid has the horizontal flip flag set.
+    const WALLS: &str = r#"{
+        "width": 3, "height": 2, "tilewidth": 16, "tileheight": 32,
+        "tilesets": [
+            { "firstgid": 1, "tilewidth": 16, "tileheight": 32, "tiles": [
+                { "id": 4, "properties": [
+                    { "name": "collision", "type": "string", "value": "solid" } ] },
+                { "id": 6, "properties": [
+                    { "name": "collision", "type": "string", "value": "solid" } ] }
+            ] }
+        ],
+        "layers": [
+            { "name": "Walls", "type": "tilelayer", "width": 3, "height": 2,
+              "offsetx": 8, "offsety": -4,
+              "data": [0, 5, 0, 2147483655, 0, 0] }
+        ]
+    }"#;
+
+    fn walls() -> World {
+        let map = MapData::from_json(WALLS).unwrap();
+        let mut world = World::new();
+        map.add_collision_layers(&mut world);
+        world
+    }
+
+    #[test]
+    fn rectangular_tiles_are_placed_at_the_layer_offset() {
+        let world = walls();
+
+        // tile (1, 0) covers x 24..40, y -4..28
+        assert!(world.tag_at(vec2(24.0, -4.0), CollisionLayers::SOLID));
+        assert!(world.tag_at(vec2(39.9, 27.9), CollisionLayers::SOLID));
+        assert!(!world.tag_at(vec2(23.9, 0.0), CollisionLayers::SOLID));
+        assert!(!world.tag_at(vec2(30.0, 28.0), CollisionLayers::SOLID));
+        assert!(!world.tag_at(vec2(30.0, -4.1), CollisionLayers::SOLID));
+        // flipped tile (0, 1) covers x 8..24, y 28..60
+        assert!(world.tag_at(vec2(8.0, 59.9), CollisionLayers::SOLID));
+        assert!(!world.tag_at(vec2(7.9, 40.0), CollisionLayers::SOLID));
+    }
+
+    #[test]
+    fn boxes_collide_with_offset_rectangular_tiles() {
+        let world = walls();
+        let collides = |pos: Vec2, width, height| {
+            world.collide_solids(CollisionLayers::SOLID, pos, width, height) != Tile::Empty
+        };
+
+        // a 4x4 box in the gap right of tile (0, 1), under tile (1, 0)
+        assert!(!collides(vec2(24.0, 28.0), 4, 4));
+        assert!(collides(vec2(23.0, 28.0), 4, 4));
+        assert!(collides(vec2(24.0, 27.0), 4, 4));
+        // tall box spanning both rows left of the layer
+        assert!(!collides(vec2(0.0, -4.0), 8, 64));
+        assert!(collides(vec2(1.0, -4.0), 8, 64));
+    }
+
+    #[test]
+    fn raycasts_hit_offset_rectangular_tiles() {
+        let world = walls();
+
+        let hit = world
+            .raycast(vec2(0.0, 40.0), vec2(1.0, 0.0), 100.0)
+            .unwrap();
+        assert_eq!(hit.point, vec2(8.0, 40.0));
+        assert_eq!(hit.normal, vec2(-1.0, 0.0));
+
+        let hit = world
+            .raycast(vec2(30.0, 100.0), vec2(0.0, -1.0), 200.0)
+            .unwrap();
+        assert_eq!(hit.point, vec2(30.0, 28.0));
+        assert_eq!(hit.normal, vec2(0.0, 1.0));
+        assert_eq!(hit.distance, 72.0);
+    }
+
+    // Wall tile 0 is solid on its right 5 pixels, tile 1 is a slope, tile 2
+    // has no shapes. The second cell is tile 0 flipped horizontally.
+    const SHAPES: &str = r#"{
+        "width": 4, "height": 1, "tilewidth": 16, "tileheight": 16,
+        "tilesets": [
+            { "firstgid": 1, "tilewidth": 16, "tileheight": 16, "tiles": [
+                { "id": 0,
+                  "properties": [ { "name": "collision", "value": "solid" } ],
+                  "objectgroup": { "objects": [
+                    { "x": 11, "y": 0, "width": 5, "height": 16, "rotation": 0 } ] } },
+                { "id": 1,
+                  "properties": [ { "name": "collision", "value": "solid" } ],
+                  "objectgroup": { "objects": [
+                    { "x": 0, "y": 16, "polygon": [
+                        { "x": 0, "y": 0 }, { "x": 16, "y": -16 }, { "x": 16, "y": 0 } ] },
+                    { "x": 8, "y": 8, "point": true } ] } },
+                { "id": 2,
+                  "properties": [ { "name": "collision", "value": "solid" } ] }
+            ] }
+        ],
+        "layers": [
+            { "name": "Walls", "type": "tilelayer", "width": 4, "height": 1,
+              "data": [1, 2147483649, 2, 3] }
+        ]
+    }"#;
+
+    #[test]
+    fn tileset_objectgroups_become_tile_shapes() {
+        let map = MapData::from_json(SHAPES).unwrap();
+
+        assert_eq!(
+            map.tile_shapes(1),
+            vec![TileShape::Rect(Rect::new(11.0, 0.0, 5.0, 16.0))]
+        );
+        assert_eq!(
+            map.tile_shapes(1 | FLIPPED_HORIZONTALLY),
+            vec![TileShape::Rect(Rect::new(0.0, 0.0, 5.0, 16.0))]
+        );
+        // the point object is not a shape
+        assert_eq!(
+            map.tile_shapes(2),
+            vec![TileShape::Polygon(vec![
+                vec2(0.0, 16.0),
+                vec2(16.0, 0.0),
+                vec2(16.0, 16.0)
+            ])]
+        );
+        assert!(map.tile_shapes(3).is_empty());
+    }
+
+    // 16x32 tree in a 16x16 grid, on the second row. Its shape is the top
+    // half of the tree, which Tiled draws over the first row.
+    const TALL_TREE: &str = r#"{
+        "width": 1, "height": 2, "tilewidth": 16, "tileheight": 16,
+        "tilesets": [
+            { "firstgid": 1, "tilewidth": 16, "tileheight": 32, "tiles": [
+                { "id": 0,
+                  "properties": [ { "name": "collision", "value": "solid" } ],
+                  "objectgroup": { "objects": [
+                    { "x": 4, "y": 0, "width": 8, "height": 16, "rotation": 0 } ] } }
+            ] }
+        ],
+        "layers": [
+            { "name": "Trees", "type": "tilelayer", "width": 1, "height": 2,
+              "data": [0, 1] }
+        ]
+    }"#;
+
+    #[test]
+    fn tall_tiles_stick_out_of_the_top_of_their_cell() {
+        let map = MapData::from_json(TALL_TREE).unwrap();
+        assert_eq!(
+            map.tile_shapes(1),
+            [TileShape::Rect(Rect::new(4.0, -16.0, 8.0, 16.0))]
+        );
+
+        let mut world = World::new();
+        map.add_collision_layers(&mut world);
+        // the shape is over the empty first row, not the tree's own cell
+        assert!(world.tag_at(vec2(8.0, 8.0), CollisionLayers::SOLID));
+        assert!(!world.tag_at(vec2(8.0, 24.0), CollisionLayers::SOLID));
+        assert_ne!(
+            world.collide_solids(CollisionLayers::SOLID, vec2(0.0, 0.0), 16, 4),
+            Tile::Empty
+        );
+        let hit = world
+            .raycast(vec2(8.0, 40.0), vec2(0.0, -1.0), 100.0)
+            .unwrap();
+        assert_eq!(hit.point, vec2(8.0, 16.0));
+        assert_eq!(hit.normal, vec2(0.0, 1.0));
+    }
+
+    #[test]
+    fn collision_layers_use_tile_shapes() {
+        let map = MapData::from_json(SHAPES).unwrap();
+        let mut world = World::new();
+        map.add_collision_layers(&mut world);
+
+        assert!(!world.tag_at(vec2(10.0, 8.0), CollisionLayers::SOLID));
+        assert!(world.tag_at(vec2(12.0, 8.0), CollisionLayers::SOLID));
+        // flipped wall is solid on its left side
+        assert!(world.tag_at(vec2(18.0, 8.0), CollisionLayers::SOLID));
+        assert!(!world.tag_at(vec2(22.0, 8.0), CollisionLayers::SOLID));
+        // slope
+        assert!(!world.tag_at(vec2(36.0, 4.0), CollisionLayers::SOLID));
+        assert!(world.tag_at(vec2(44.0, 12.0), CollisionLayers::SOLID));
+        // tiles without shapes block the whole cell
+        assert!(world.tag_at(vec2(49.0, 1.0), CollisionLayers::SOLID));
+    }
+
+    #[test]
+    fn house_walls_in_the_shipped_map_are_thin() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+        let mut world = World::new();
+        map.add_collision_layers(&mut world);
+
+        // left wall column is tile x 43, only its right 5 pixels block
+        assert!(!world.tag_at(
+            vec2(43.0 * 16.0 + 4.0, 34.0 * 16.0 + 8.0),
+            CollisionLayers::SOLID
+        ));
+        assert!(world.tag_at(
+            vec2(43.0 * 16.0 + 12.0, 34.0 * 16.0 + 8.0),
+            CollisionLayers::SOLID
+        ));
+        // the player spawns clear of everything
+        assert_eq!(
+            world.collide_solids(
+                CollisionLayers::SOLID | CollisionLayers::WATER,
+                vec2(784.0, 512.0),
+                16,
+                16
+            ),
+            Tile::Empty
+        );
+        // nothing but the door blocks the doorway
+        assert_eq!(
+            world.collide_solids(CollisionLayers::ALL, vec2(784.0, 544.0), 16, 32),
+            Tile::Empty
+        );
+        // the ocean around the island is water, the island is not
+        assert!(world.tag_at(vec2(8.0, 8.0), CollisionLayers::WATER));
+        assert!(!world.tag_at(vec2(30.0 * 16.0, 30.0 * 16.0), CollisionLayers::WATER));
+    }
+
+    // Water everywhere, land with `collision=none` over the first and last
+    // cell, a wall over the last cell and a flower without properties over
+    // the first two
+    const LAYERED: &str = r#"{
+        "width": 3, "height": 1, "tilewidth": 16, "tileheight": 16,
+        "tilesets": [
+            { "firstgid": 1, "tilewidth": 16, "tileheight": 16, "tiles": [
+                { "id": 0, "properties": [ { "name": "collision", "value": "water" } ] },
+                { "id": 1, "properties": [ { "name": "collision", "value": "none" } ] },
+                { "id": 2, "properties": [ { "name": "collision", "value": "solid" } ] },
+                { "id": 4, "properties": [ { "name": "collision", "value": "lava" } ] }
+            ] }
+        ],
+        "layers": [
+            { "name": "Ocean", "type": "tilelayer", "width": 3, "height": 1,
+              "data": [1, 1, 1] },
+            { "name": "Land", "type": "tilelayer", "width": 3, "height": 1,
+              "data": [2, 0, 2] },
+            { "name": "House", "type": "tilelayer", "width": 3, "height": 1,
+              "data": [0, 0, 3] },
+            { "name": "Decor", "type": "tilelayer", "width": 3, "height": 1,
+              "data": [4, 5, 0] }
+        ]
+    }"#;
+
+    #[test]
+    fn upper_layers_override_collision_below() {
+        let map = MapData::from_json(LAYERED).unwrap();
+        let mut world = World::new();
+        map.add_collision_layers(&mut world);
+        let blocks = |x: f32, mask| world.tag_at(vec2(x, 8.0), mask);
+
+        assert!(!blocks(8.0, CollisionLayers::ALL));
+        assert!(blocks(24.0, CollisionLayers::WATER));
+        assert!(!blocks(24.0, CollisionLayers::SOLID));
+        assert!(blocks(40.0, CollisionLayers::SOLID));
+        assert!(!blocks(40.0, CollisionLayers::WATER));
+    }
+
+    #[test]
+    fn tile_collision_parses_the_property() {
+        let map = MapData::from_json(LAYERED).unwrap();
+
+        assert_eq!(map.tile_collision(1), Some(Collision::Water));
+        assert_eq!(map.tile_collision(2), Some(Collision::None));
+        assert_eq!(
+            map.tile_collision(3 | FLIPPED_VERTICALLY),
+            Some(Collision::Solid)
+        );
+        // no property, unknown value, unknown tile
+        assert_eq!(map.tile_collision(4), None);
+        assert_eq!(map.tile_collision(5), None);
+        assert_eq!(map.tile_collision(99), None);
+    }
+
+    // Object layer shifted by (0, 100), with one object of each type plus an
+    // interactable missing its action and an object of an unknown type
+    const ENTITIES: &str = r#"{
+        "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+        "layers": [
+            { "name": "Ground", "type": "tilelayer", "width": 1, "height": 1, "data": [0] },
+            { "name": "Entities", "type": "objectgroup", "offsetx": 0, "offsety": 100,
+              "objects": [
+                { "name": "player", "type": "spawn", "point": true, "x": 10, "y": 20 },
+                { "name": "porch", "type": "spawn", "x": 0, "y": 0, "width": 16, "height": 8 },
+                { "type": "door", "x": 32, "y": 48, "width": 16, "height": 16 },
+                { "type": "portal", "x": 0, "y": 64, "width": 32, "height": 8,
+                  "properties": [ { "name": "spawn", "type": "string", "value": "porch" } ] },
+                { "type": "portal", "x": 0, "y": 80, "width": 32, "height": 8,
+                  "properties": [
+                    { "name": "map", "type": "string", "value": "town" },
+                    { "name": "spawn", "type": "string", "value": "square" } ] },
+                { "type": "interact", "x": 8, "y": 8, "width": 16, "height": 16,
+                  "properties": [ { "name": "action", "type": "string", "value": "pay" } ] },
+                { "type": "interact", "x": 8, "y": 8, "width": 16, "height": 16 },
+                { "type": "pan", "point": true, "x": 40, "y": 8 },
+                { "type": "tree", "x": 0, "y": 0 }
+              ] }
+        ]
+    }"#;
+
+    #[test]
+    fn object_layers_become_entities() {
+        let map = MapData::from_json(ENTITIES).unwrap();
+
+        assert_eq!(
+            map.entities(),
+            vec![
+                Entity::Spawn {
+                    name: "player".to_owned(),
+                    position: vec2(10.0, 120.0)
+                },
+                Entity::Spawn {
+                    name: "porch".to_owned(),
+                    position: vec2(8.0, 104.0)
+                },
+                Entity::Door {
+                    rect: Rect::new(32.0, 148.0, 16.0, 16.0)
+                },
+                Entity::Portal {
+                    rect: Rect::new(0.0, 164.0, 32.0, 8.0),
+                    map: None,
+                    spawn: "porch".to_owned()
+                },
+                Entity::Portal {
+                    rect: Rect::new(0.0, 180.0, 32.0, 8.0),
+                    map: Some("town".to_owned()),
+                    spawn: "square".to_owned()
+                },
+                Entity::Interact {
+                    rect: Rect::new(8.0, 108.0, 16.0, 16.0),
+                    action: "pay".to_owned()
+                },
+                Entity::Pan {
+                    position: vec2(40.0, 108.0)
+                },
+            ]
+        );
+        assert_eq!(map.spawn("porch"), Some(vec2(8.0, 104.0)));
+        assert_eq!(map.spawn("square"), None);
+    }
+
+    #[test]
+    fn shipped_trees_and_furniture_sort_as_whole_objects() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+        let sorted: Vec<&LayerData> = map.layers.iter().filter(|layer| layer.ysort()).collect();
+        assert_eq!(sorted.len(), 1);
+        let furniture = sorted[0];
+        assert_eq!(furniture.name, "Furniture");
+
+        // every tile of the big tree west of the house sorts by its trunk
+        for y in 31..=33 {
+            for x in 30..=32 {
+                assert_eq!(map.depth(furniture, x, y), 34.0 * 16.0, "({x}, {y})");
+            }
+        }
+        // and the tall piece in the house by its bottom tile
+        for y in 31..=33 {
+            assert_eq!(map.depth(furniture, 45, y), 34.0 * 16.0, "(45, {y})");
+        }
+        // no plants are left on layers drawn under the player
+        let firstgid = |name: &str| {
+            let tileset = map.tilesets.iter().find(|tileset| tileset.name == name);
+            tileset.unwrap().firstgid
+        };
+        let plants = firstgid("plants")..firstgid("Tilled_Dirt");
+        for layer in map.layers.iter().filter(|layer| !layer.ysort()) {
+            assert!(
+                !layer.tiles().any(|(_, _, gid)| plants.contains(&gid)),
+                "{}",
+                layer.name
+            );
+        }
+    }
+
+    #[test]
+    fn animated_tiles_loop_through_their_frames() {
+        let map = MapData::from_json(
+            r#"{
+            "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+            "tilesets": [
+                { "name": "Crops", "firstgid": 1, "tilewidth": 16, "tileheight": 16, "tiles": [
+                    { "id": 2, "animation": [
+                        { "tileid": 2, "duration": 100 },
+                        { "tileid": 3, "duration": 300 } ] }
+                ] }
+            ],
+            "layers": []
+        }"#,
+        )
+        .unwrap();
+        let animations = map.tile_animations();
+
+        assert_eq!(animations.frame("Crops", 2, 0.0), 2);
+        assert_eq!(animations.frame("Crops", 2, 0.099), 2);
+        assert_eq!(animations.frame("Crops", 2, 0.1), 3);
+        assert_eq!(animations.frame("Crops", 2, 0.399), 3);
+        // loops
+        assert_eq!(animations.frame("Crops", 2, 0.45), 2);
+        // tiles without animation are left alone
+        assert_eq!(animations.frame("Crops", 1, 0.2), 1);
+        assert_eq!(animations.frame("Water", 2, 0.2), 2);
+    }
+
+    #[test]
+    fn shipped_water_shimmers() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+        let animations = map.tile_animations();
+        let frames: Vec<u32> = (0..4)
+            .map(|frame| animations.frame("Water", 0, frame as f64 * 0.25))
+            .collect();
+        assert_eq!(frames, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn infinite_maps_keep_their_chunks() {
+        // one wall left of the origin, one water tile far down right, and an
+        // empty chunk
+        let mut wall = vec![0; 256];
+        wall[15] = 1;
+        let mut water = vec![0; 256];
+        water[16 + 2] = 2;
+        let json = format!(
+            r#"{{
+            "width": 32, "height": 16, "tilewidth": 16, "tileheight": 16, "infinite": true,
+            "tilesets": [
+                {{ "name": "Walls", "firstgid": 1, "tilewidth": 16, "tileheight": 16, "tiles": [
+                    {{ "id": 0, "properties": [
+                        {{ "name": "collision", "type": "string", "value": "solid" }} ] }},
+                    {{ "id": 1, "properties": [
+                        {{ "name": "collision", "type": "string", "value": "water" }} ] }}
+                ] }}
+            ],
+            "layers": [
+                {{ "name": "Land", "type": "tilelayer", "width": 32, "height": 16,
+                  "chunks": [
+                    {{ "x": -16, "y": -16, "width": 16, "height": 16, "data": {wall:?} }},
+                    {{ "x": 32, "y": 16, "width": 16, "height": 16, "data": {water:?} }},
+                    {{ "x": 0, "y": 0, "width": 16, "height": 16, "data": {:?} }} ] }}
+            ]
+        }}"#,
+            vec![0; 256]
+        );
+        let map = MapData::from_json(&json).unwrap();
+        let land = &map.layers[0];
+
+        assert_eq!(land.chunks.len(), 2);
+        assert_eq!(land.raw_gid(-1, -16), 1);
+        assert_eq!(land.raw_gid(34, 17), 2);
+        assert_eq!(land.raw_gid(0, 0), 0);
+        assert_eq!(map.chunk_extent(), (-1..3, -1..2));
+        assert_eq!(map.rect(), Rect::new(-256.0, -256.0, 1024.0, 768.0));
+
+        let mut world = World::new();
+        map.add_collision_layers(&mut world);
+        assert!(world.tag_at(vec2(-8.0, -248.0), CollisionLayers::SOLID));
+        assert!(world.tag_at(vec2(552.0, 280.0), CollisionLayers::WATER));
+        assert!(!world.tag_at(vec2(8.0, 8.0), CollisionLayers::SOLID));
+    }
+
+    #[test]
+    fn fixed_size_maps_are_cut_into_chunks() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(include_str!("../assets/map.json")).unwrap();
+
+        for (layer, source) in map.layers.iter().zip(json["layers"].as_array().unwrap()) {
+            let Some(data) = source["data"].as_array() else {
+                continue;
+            };
+            for (index, gid) in data.iter().enumerate() {
+                let (x, y) = (
+                    (index as u32 % map.width) as i32,
+                    (index as u32 / map.width) as i32,
+                );
+                assert_eq!(
+                    layer.raw_gid(x, y) as u64,
+                    gid.as_u64().unwrap(),
+                    "{} ({x}, {y})",
+                    layer.name
+                );
+            }
+            assert!(layer
+                .chunks
+                .values()
+                .all(|cells| cells.iter().any(|&gid| gid != 0)));
+        }
+        let columns = map.width.div_ceil(CHUNK_SIZE as u32) as i32;
+        let rows = map.height.div_ceil(CHUNK_SIZE as u32) as i32;
+        assert_eq!(map.chunk_extent(), (0..columns, 0..rows));
+    }
+
+    #[test]
+    fn tile_sprites_find_their_tileset_and_source() {
+        let map = MapData::from_json(
+            r#"{
+            "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+            "tilesets": [
+                { "name": "Grass", "firstgid": 1, "tilewidth": 16, "tileheight": 16,
+                  "image": "Grass.png", "columns": 4, "tilecount": 8 },
+                { "name": "Trees", "firstgid": 9, "tilewidth": 32, "tileheight": 48,
+                  "image": "Trees.png", "columns": 3, "tilecount": 6, "margin": 1, "spacing": 2 }
+            ],
+            "layers": []
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(map.tile_sprite(0), None);
+        let grass = map.tile_sprite(6).unwrap();
+        assert_eq!((grass.tileset, grass.id), (0, 5));
+        assert_eq!(grass.flips(), (false, false, false));
+        assert_eq!(
+            map.tilesets[0].source(grass.id),
+            Rect::new(16.0, 16.0, 16.0, 16.0)
+        );
+
+        let tree = map
+            .tile_sprite(13 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY)
+            .unwrap();
+        assert_eq!((tree.tileset, tree.id), (1, 4));
+        assert_eq!(tree.flips(), (true, false, true));
+        assert_eq!(
+            map.tilesets[1].source(tree.id),
+            Rect::new(35.0, 51.0, 32.0, 48.0)
+        );
+        // past the last tile of the last tileset
+        assert_eq!(map.tile_sprite(15), None);
+    }
+
+    #[test]
+    fn collision_regions_match_the_whole_map() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+        let mut whole = World::new();
+        map.add_collision_layers(&mut whole);
+        let mut chunked = World::new();
+        let (columns, rows) = map.chunk_extent();
+        for y in rows {
+            for x in columns.clone() {
+                let (x, y) = (x * CHUNK_SIZE, y * CHUNK_SIZE);
+                map.add_collision_region(&mut chunked, x, y, CHUNK_SIZE, CHUNK_SIZE);
+            }
+        }
+
+        // around the house, which straddles the chunk border at tile x 48
+        let mask = CollisionLayers::SOLID | CollisionLayers::WATER;
+        for y in (28 * 16..42 * 16).step_by(3) {
+            for x in (40 * 16..58 * 16).step_by(3) {
+                let pos = vec2(x as f32, y as f32);
+                assert_eq!(whole.tag_at(pos, mask), chunked.tag_at(pos, mask), "{pos}");
+                assert_eq!(
+                    whole.collide_solids(mask, pos, 16, 16),
+                    chunked.collide_solids(mask, pos, 16, 16),
+                    "{pos}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn removed_regions_stop_colliding() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+        let mut world = World::new();
+        let ocean = vec2(8.0, 8.0);
+        let layers = map.add_collision_region(&mut world, 0, 0, 16, 16);
+        assert!(world.tag_at(ocean, CollisionLayers::WATER));
+        // only the region is loaded
+        assert!(!world.tag_at(vec2(16.0 * 16.0 + 8.0, 8.0), CollisionLayers::WATER));
+
+        for layer in layers {
+            world.remove_static_tiled_layer(layer);
+        }
+        assert!(!world.tag_at(ocean, CollisionLayers::WATER));
+    }
+
+    #[test]
+    fn shipped_map_places_the_player_door_and_playtable() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+
+        assert_eq!(map.spawn("player"), Some(vec2(792.0, 520.0)));
+        let entities = map.entities();
+        assert!(entities.contains(&Entity::Door {
+            rect: Rect::new(784.0, 560.0, 16.0, 16.0)
+        }));
+        assert!(entities.contains(&Entity::Interact {
+            rect: Rect::new(728.0, 536.0, 16.0, 16.0),
+            action: "pay".to_owned()
+        }));
+    }
+
+    // 6x4 map with land on cells (1, 1) to (4, 2), layer shifted by (3, 0)
+    const ISLAND: &str = r#"{
+        "width": 6, "height": 4, "tilewidth": 16, "tileheight": 8,
+        "layers": [
+            { "name": "Ocean", "type": "tilelayer", "width": 6, "height": 4,
+              "data": [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+                       1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1] },
+            { "name": "Land", "type": "tilelayer", "width": 6, "height": 4, "offsetx": 3,
+              "data": [0, 0, 0, 0, 0, 0, 0, 2, 2, 2, 0, 0,
+                       0, 0, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0] }
+        ]
+    }"#;
+
+    #[test]
+    fn bounds_follow_the_land_layer() {
+        let map = MapData::from_json(ISLAND).unwrap();
+
+        assert_eq!(map.bounds(), Rect::new(19.0, 8.0, 64.0, 16.0));
+    }
+
+    #[test]
+    fn bounds_object_overrides_the_land_layer() {
+        let mut map = MapData::from_json(ISLAND).unwrap();
+        map.layers.push(
+            serde_json::from_str(
+                r#"{ "name": "Entities", "type": "objectgroup", "offsety": 2,
+                     "objects": [ { "name": "bounds", "x": 4, "y": 6, "width": 30, "height": 10 } ] }"#,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(map.bounds(), Rect::new(4.0, 8.0, 30.0, 10.0));
+        // the bounds object is not an entity
+        assert!(map.entities().is_empty());
+    }
+
+    #[test]
+    fn bounds_default_to_the_whole_map() {
+        let mut map = MapData::from_json(ISLAND).unwrap();
+        map.layers.retain(|layer| layer.name != "Land");
+
+        assert_eq!(map.bounds(), Rect::new(0.0, 0.0, 96.0, 32.0));
+    }
+
+    #[test]
+    fn shipped_map_bounds_cover_the_island() {
+        let map = MapData::from_json(include_str!("../assets/map.json")).unwrap();
+
+        // the 32x32 tile island in the middle of the 90x90 map
+        assert_eq!(map.bounds(), Rect::new(464.0, 464.0, 512.0, 512.0));
+    }
+}
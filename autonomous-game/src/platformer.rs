@@ -1,6 +1,23 @@
 use macroquad::math::{vec2, Rect, Vec2};
 
-use std::collections::HashSet;
+mod path;
+mod query;
+mod shape;
+mod spatial_hash;
+#[cfg(test)]
+mod tests;
+mod trigger;
+
+pub use query::Hit;
+use shape::Convex;
+pub use shape::TileShape;
+use spatial_hash::SpatialHash;
+use trigger::TriggerVolume;
+pub use trigger::{Trigger, TriggerEvent, TriggerEventKind};
+
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::ops::{BitOr, BitOrAssign, Index, IndexMut, RangeInclusive};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Tile {
@@ -21,23 +38,179 @@ impl Tile {
         }
     }
 }
+
+/// Bitmask of collision layers. Tile layers and solids belong to a category,
+/// actors are blocked by everything their mask intersects.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct CollisionLayers(pub u32);
+
+impl CollisionLayers {
+    pub const NONE: CollisionLayers = CollisionLayers(0);
+    /// Walls and other terrain, what the former tag `1` stood for
+    pub const SOLID: CollisionLayers = CollisionLayers(1 << 0);
+    /// Deep water, blocks walkers only
+    pub const WATER: CollisionLayers = CollisionLayers(1 << 1);
+    /// Default category of actors. Actors never block movement, this bit only
+    /// makes them visible to `raycast` and `sweep_box`.
+    pub const ACTOR: CollisionLayers = CollisionLayers(1 << 31);
+    pub const ALL: CollisionLayers = CollisionLayers(u32::MAX);
+
+    pub fn intersects(self, other: CollisionLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for CollisionLayers {
+    type Output = CollisionLayers;
+
+    fn bitor(self, other: CollisionLayers) -> CollisionLayers {
+        CollisionLayers(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for CollisionLayers {
+    fn bitor_assign(&mut self, other: CollisionLayers) {
+        self.0 |= other.0;
+    }
+}
+
 pub struct StaticTiledLayer {
     static_colliders: Vec<Tile>,
     tile_width: f32,
     tile_height: f32,
     width: usize,
-    tag: u8,
+    // world position of the layer's top left corner
+    offset: Vec2,
+    // cells that only block part of their area, by index into `static_colliders`
+    shapes: HashMap<usize, Vec<Convex>>,
+    // how many cells past their own the shapes reach, like the parts of
+    // tiles larger than the grid that stick out of their cell
+    reach: i32,
+    category: CollisionLayers,
+}
+
+impl StaticTiledLayer {
+    fn tile(&self, x: i32, y: i32) -> Tile {
+        if x < 0 || y < 0 || x >= self.width as i32 {
+            return Tile::Empty;
+        }
+        self.static_colliders
+            .get(y as usize * self.width + x as usize)
+            .copied()
+            .unwrap_or(Tile::Empty)
+    }
+
+    /// Grid cell containing a world position, may be outside the layer.
+    fn cell(&self, pos: Vec2) -> (i32, i32) {
+        let local = pos - self.offset;
+        (
+            (local.x / self.tile_width).floor() as i32,
+            (local.y / self.tile_height).floor() as i32,
+        )
+    }
+
+    /// Tile under a world position.
+    fn tile_at(&self, pos: Vec2) -> Tile {
+        let (x, y) = self.cell(pos);
+        let tile = self.tile(x, y);
+        if tile != Tile::Empty && self.cell_shapes(x, y).is_none() {
+            return tile;
+        }
+        self.shapes_near(x, y)
+            .find(|(_, shape)| shape.contains(pos))
+            .map_or(Tile::Empty, |(tile, _)| tile)
+    }
+
+    /// Cells touching `rect`, and the ones around them whose shapes can
+    /// reach into it.
+    fn cells_touching(&self, rect: Rect) -> (RangeInclusive<i32>, RangeInclusive<i32>) {
+        let (x0, y0) = self.cell(rect.point());
+        let (x1, y1) = self.cell(vec2(rect.right(), rect.bottom()));
+        (
+            x0 - self.reach..=x1 + self.reach,
+            y0 - self.reach..=y1 + self.reach,
+        )
+    }
+
+    /// Shapes that can reach into cell (`x`, `y`), with the tile of their cell.
+    fn shapes_near(&self, x: i32, y: i32) -> impl Iterator<Item = (Tile, &Convex)> {
+        let reach = self.reach;
+        (y - reach..=y + reach)
+            .flat_map(move |y| (x - reach..=x + reach).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.tile(x, y) != Tile::Empty)
+            .flat_map(move |(x, y)| {
+                let tile = self.tile(x, y);
+                self.cell_shapes(x, y)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(move |shape| (tile, shape))
+            })
+    }
+
+    /// Collision shapes of an in-bounds cell, `None` if it blocks the whole cell.
+    fn cell_shapes(&self, x: i32, y: i32) -> Option<&[Convex]> {
+        if x < 0 || y < 0 || x >= self.width as i32 {
+            return None;
+        }
+        self.shapes
+            .get(&(y as usize * self.width + x as usize))
+            .map(Vec::as_slice)
+    }
+
+    /// Tile of the cell if its collision shape overlaps `rect`, `Tile::Empty` otherwise.
+    fn tile_overlapping(&self, x: i32, y: i32, rect: &Rect) -> Tile {
+        let tile = self.tile(x, y);
+        if tile == Tile::Empty {
+            return tile;
+        }
+        let hit = match self.cell_shapes(x, y) {
+            Some(shapes) => shapes.iter().any(|shape| shape.overlaps(rect)),
+            None => overlaps(&self.tile_rect(x, y), rect),
+        };
+        if hit {
+            tile
+        } else {
+            Tile::Empty
+        }
+    }
+
+    fn tile_rect(&self, x: i32, y: i32) -> Rect {
+        Rect::new(
+            self.offset.x + x as f32 * self.tile_width,
+            self.offset.y + y as f32 * self.tile_height,
+            self.tile_width,
+            self.tile_height,
+        )
+    }
+
+    /// Number of rows, the last one may be partial.
+    fn height(&self) -> usize {
+        self.static_colliders.len().div_ceil(self.width)
+    }
 }
 
+/// Size of a broadphase cell, in pixels. A few tiles wide so most colliders
+/// touch at most four cells.
+const BROADPHASE_CELL_SIZE: f32 = 64.0;
+
 pub struct World {
-    static_tiled_layers: Vec<StaticTiledLayer>,
-    solids: Vec<(Solid, Collider)>,
-    actors: Vec<(Actor, Collider)>,
+    static_tiled_layers: Arena<TiledLayer, StaticTiledLayer>,
+    solids: Arena<Solid, Collider>,
+    actors: Arena<Actor, Collider>,
+    triggers: Arena<Trigger, TriggerVolume>,
+    solid_grid: SpatialHash<Solid>,
+    actor_grid: SpatialHash<Actor>,
+    // actors currently squished by each solid, the reverse of `Collider::squishers`
+    squished_by: HashMap<Solid, HashSet<Actor>>,
 }
 
 #[derive(Clone, Debug)]
 struct Collider {
     collidable: bool,
+    // layers this collider belongs to
+    category: CollisionLayers,
+    // layers that block this collider, only used for actors
+    mask: CollisionLayers,
     squished: bool,
     pos: Vec2,
     width: i32,
@@ -60,18 +233,199 @@ impl Collider {
     }
 }
 
+/// Whether the interiors of two rects intersect. Unlike `Rect::overlaps`,
+/// rects that only share an edge don't overlap, so an actor can slide along
+/// a solid it is touching.
+fn overlaps(a: &Rect, b: &Rect) -> bool {
+    a.left() < b.right() && a.right() > b.left() && a.top() < b.bottom() && a.bottom() > b.top()
+}
+
+/// Generational handle to an actor. Handles of removed actors never alias a
+/// later actor that reuses the same slot.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-pub struct Actor(usize);
+pub struct Actor {
+    index: usize,
+    generation: u32,
+}
+
+/// Generational handle to a solid, see [`Actor`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Solid {
+    index: usize,
+    generation: u32,
+}
 
+/// Generational handle to a static tiled layer, see [`Actor`].
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-pub struct Solid(usize);
+pub struct TiledLayer {
+    index: usize,
+    generation: u32,
+}
+
+trait Handle: Copy {
+    const NAME: &'static str;
+
+    fn from_raw(index: usize, generation: u32) -> Self;
+    fn index(&self) -> usize;
+    fn generation(&self) -> u32;
+}
+
+/// Implements `Handle` for a struct with `index` and `generation` fields.
+macro_rules! impl_handle {
+    ($name:ident) => {
+        impl Handle for $name {
+            const NAME: &'static str = stringify!($name);
+
+            fn from_raw(index: usize, generation: u32) -> Self {
+                $name { index, generation }
+            }
+            fn index(&self) -> usize {
+                self.index
+            }
+            fn generation(&self) -> u32 {
+                self.generation
+            }
+        }
+    };
+}
+use impl_handle;
+
+impl_handle!(Actor);
+impl_handle!(Solid);
+impl_handle!(TiledLayer);
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Slot map keyed by generational handles. Removing a value bumps the slot
+/// generation, so stale handles are rejected instead of pointing at whatever
+/// gets inserted into the slot next.
+struct Arena<K, T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    _handle: PhantomData<K>,
+}
+
+impl<K: Handle, T> Arena<K, T> {
+    fn new() -> Self {
+        Arena {
+            slots: vec![],
+            free: vec![],
+            _handle: PhantomData,
+        }
+    }
+
+    fn insert(&mut self, value: T) -> K {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: None,
+                });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        let handle = K::from_raw(index, slot.generation);
+        slot.value = Some(value);
+        handle
+    }
+
+    fn remove(&mut self, handle: K) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index())?;
+        if slot.generation != handle.generation() || slot.value.is_none() {
+            return None;
+        }
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index());
+        slot.value.take()
+    }
+
+    fn get(&self, handle: K) -> Option<&T> {
+        self.slots
+            .get(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    fn get_mut(&mut self, handle: K) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    fn contains(&self, handle: K) -> bool {
+        self.get(handle).is_some()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (K, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let value = slot.value.as_ref()?;
+            Some((K::from_raw(index, slot.generation), value))
+        })
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (K, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+                let value = slot.value.as_mut()?;
+                Some((K::from_raw(index, generation), value))
+            })
+    }
+}
+
+impl<K: Handle, T> Index<K> for Arena<K, T> {
+    type Output = T;
+
+    fn index(&self, handle: K) -> &T {
+        self.get(handle)
+            .unwrap_or_else(|| panic!("stale or invalid {} handle", K::NAME))
+    }
+}
+
+impl<K: Handle, T> IndexMut<K> for Arena<K, T> {
+    fn index_mut(&mut self, handle: K) -> &mut T {
+        self.get_mut(handle)
+            .unwrap_or_else(|| panic!("stale or invalid {} handle", K::NAME))
+    }
+}
+
+/// Which axes of a `World::move_actor` call were stopped by a collision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveResult {
+    pub blocked_x: bool,
+    pub blocked_y: bool,
+}
+
+impl MoveResult {
+    pub fn blocked(&self) -> bool {
+        self.blocked_x || self.blocked_y
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
+}
 
 impl World {
     pub fn new() -> World {
         World {
-            static_tiled_layers: vec![],
-            actors: vec![],
-            solids: vec![],
+            static_tiled_layers: Arena::new(),
+            actors: Arena::new(),
+            solids: Arena::new(),
+            triggers: Arena::new(),
+            solid_grid: SpatialHash::new(BROADPHASE_CELL_SIZE),
+            actor_grid: SpatialHash::new(BROADPHASE_CELL_SIZE),
+            squished_by: HashMap::new(),
         }
     }
 
@@ -81,116 +435,252 @@ impl World {
         tile_width: f32,
         tile_height: f32,
         width: usize,
-        tag: u8,
-    ) {
-        self.static_tiled_layers.push(StaticTiledLayer {
+        category: CollisionLayers,
+    ) -> TiledLayer {
+        self.add_static_tiled_layer_at(
+            Vec2::ZERO,
             static_colliders,
             tile_width,
             tile_height,
             width,
-            tag,
-        });
+            category,
+        )
     }
-    pub fn add_actor(&mut self, pos: Vec2, width: i32, height: i32) -> Actor {
-        let actor = Actor(self.actors.len());
 
+    /// Same as `add_static_tiled_layer`, with the layer's top left corner at
+    /// `offset` instead of the world origin (Tiled's layer `offsetx`/`offsety`).
+    pub fn add_static_tiled_layer_at(
+        &mut self,
+        offset: Vec2,
+        static_colliders: Vec<Tile>,
+        tile_width: f32,
+        tile_height: f32,
+        width: usize,
+        category: CollisionLayers,
+    ) -> TiledLayer {
+        self.static_tiled_layers.insert(StaticTiledLayer {
+            static_colliders,
+            tile_width,
+            tile_height,
+            width,
+            offset,
+            shapes: HashMap::new(),
+            reach: 0,
+            category,
+        })
+    }
+
+    /// Removes a static layer, e.g. a map chunk that went out of view.
+    /// Returns `false` if it was already removed.
+    pub fn remove_static_tiled_layer(&mut self, layer: TiledLayer) -> bool {
+        self.static_tiled_layers.remove(layer).is_some()
+    }
+
+    /// Makes tile (`x`, `y`) of a static layer block only `shapes`, relative
+    /// to the cell's top left corner, instead of its whole cell. Shapes can
+    /// stick out of the cell. An empty slice restores the whole cell.
+    pub fn set_tile_shapes(&mut self, layer: TiledLayer, x: usize, y: usize, shapes: &[TileShape]) {
+        let layer = &mut self.static_tiled_layers[layer];
+        assert!(
+            x < layer.width && y < layer.height(),
+            "tile ({x}, {y}) is outside the layer"
+        );
+        let index = y * layer.width + x;
+        if shapes.is_empty() {
+            layer.shapes.remove(&index);
+            return;
+        }
+
+        let cell = layer.tile_rect(x as i32, y as i32);
+        let shapes: Vec<Convex> = shapes
+            .iter()
+            .filter_map(|shape| Convex::new(shape, cell.point()))
+            .collect();
+        for shape in &shapes {
+            let bounds = shape.bounds();
+            let cells = |past: f32, size: f32| (past / size - 1e-3).ceil().max(0.0) as i32;
+            layer.reach = layer
+                .reach
+                .max(cells(cell.left() - bounds.left(), layer.tile_width))
+                .max(cells(bounds.right() - cell.right(), layer.tile_width))
+                .max(cells(cell.top() - bounds.top(), layer.tile_height))
+                .max(cells(bounds.bottom() - cell.bottom(), layer.tile_height));
+        }
+        layer.shapes.insert(index, shapes);
+    }
+
+    /// Removes every static layer, e.g. to load another map.
+    pub fn clear_static_tiled_layers(&mut self) {
+        let layers: Vec<TiledLayer> = self
+            .static_tiled_layers
+            .iter()
+            .map(|(layer, _)| layer)
+            .collect();
+        for layer in layers {
+            self.static_tiled_layers.remove(layer);
+        }
+    }
+
+    /// Adds an actor in the `ACTOR` category, blocked by `SOLID`.
+    pub fn add_actor(&mut self, pos: Vec2, width: i32, height: i32) -> Actor {
         let mut descent = false;
         let mut seen_wood = false;
-        let tile = self.collide_solids(pos, width, height);
+        let tile = self.collide_solids(CollisionLayers::SOLID, pos, width, height);
         if tile == Tile::JumpThrough {
             descent = true;
             seen_wood = true;
         }
-        self.actors.push((
-            actor,
-            Collider {
-                collidable: true,
-                squished: false,
-                pos,
-                width,
-                height,
-                x_remainder: 0.,
-                y_remainder: 0.,
-                squishers: HashSet::new(),
-                descent,
-                seen_wood,
-            },
-        ));
+        let collider = Collider {
+            collidable: true,
+            category: CollisionLayers::ACTOR,
+            mask: CollisionLayers::SOLID,
+            squished: false,
+            pos,
+            width,
+            height,
+            x_remainder: 0.,
+            y_remainder: 0.,
+            squishers: HashSet::new(),
+            descent,
+            seen_wood,
+        };
+        let rect = collider.rect();
+        let actor = self.actors.insert(collider);
+        self.actor_grid.insert(actor, rect);
 
         actor
     }
-    pub fn remove_actor(&mut self, actor: Actor) {
-        // Check if the actor index is valid
-        if actor.0 < self.actors.len() {
-            // Remove the actor from the actors vector
-            // This is more complex than just removing the element because
-            // we need to update the indices stored in all Actor structs that come after
-            self.actors.remove(actor.0);
-
-            // Update the indices of all actors that came after the removed one
-            for i in actor.0..self.actors.len() {
-                self.actors[i].0 = Actor(i);
-            }
-
-            // Additionally, we might need to update any references to this actor
-            // in other data structures like the squishers set in solids
-            for (_, collider) in &mut self.solids {
-                collider
-                    .squishers
-                    .retain(|&squisher_actor| squisher_actor.0 != actor.0);
-                // Note that actors with indices > actor.0 would need to be adjusted,
-                // but since Actor doesn't implement Ord, we can't easily do this with a HashSet
-                // A more complex solution might be needed depending on your requirements
+    /// Removes the actor. Returns `false` if the handle was already stale.
+    pub fn remove_actor(&mut self, actor: Actor) -> bool {
+        let Some(collider) = self.actors.remove(actor) else {
+            return false;
+        };
+        self.actor_grid.remove(actor, collider.rect());
+        for solid in &collider.squishers {
+            if let Some(victims) = self.squished_by.get_mut(solid) {
+                victims.remove(&actor);
             }
         }
+        true
     }
 
+    pub fn contains_actor(&self, actor: Actor) -> bool {
+        self.actors.contains(actor)
+    }
+
+    /// Layers that block the actor's movement.
+    pub fn set_actor_mask(&mut self, actor: Actor, mask: CollisionLayers) {
+        self.actors[actor].mask = mask;
+    }
+
+    pub fn actor_mask(&self, actor: Actor) -> CollisionLayers {
+        self.actors[actor].mask
+    }
+
+    pub fn set_actor_category(&mut self, actor: Actor, category: CollisionLayers) {
+        self.actors[actor].category = category;
+    }
+
+    /// Adds a solid in the `SOLID` category.
     pub fn add_solid(&mut self, pos: Vec2, width: i32, height: i32) -> Solid {
-        let solid = Solid(self.solids.len());
-
-        self.solids.push((
-            solid,
-            Collider {
-                collidable: true,
-                squished: false,
-                pos,
-                width,
-                height,
-                x_remainder: 0.,
-                y_remainder: 0.,
-                squishers: HashSet::new(),
-                descent: false,
-                seen_wood: false,
-            },
-        ));
+        let collider = Collider {
+            collidable: true,
+            category: CollisionLayers::SOLID,
+            mask: CollisionLayers::NONE,
+            squished: false,
+            pos,
+            width,
+            height,
+            x_remainder: 0.,
+            y_remainder: 0.,
+            squishers: HashSet::new(),
+            descent: false,
+            seen_wood: false,
+        };
+        let rect = collider.rect();
+        let solid = self.solids.insert(collider);
+        self.solid_grid.insert(solid, rect);
 
         solid
     }
 
+    /// Removes the first solid placed exactly at `pos`. Prefer keeping the
+    /// handle from `add_solid` and calling `remove_solid_handle`.
     pub fn remove_solid(&mut self, pos: Vec2) -> Option<Solid> {
-        // Find the index of the solid at the given position
-        let index = self.solids.iter().position(|(_, collider)| {
-            // Check if the positions match exactly
-            collider.pos == pos
-        });
-
-        // If a solid was found at that position, remove it and return its ID
-        if let Some(idx) = index {
-            let solid = self.solids.remove(idx).0;
-
-            // Update the indices of all solids that come after the removed one
-            for i in idx..self.solids.len() {
-                self.solids[i].0 = Solid(i);
-            }
+        // Find the solid at the given position
+        let solid = self
+            .solids
+            .iter()
+            .find(|(_, collider)| collider.pos == pos)
+            .map(|(solid, _)| solid)?;
 
-            Some(solid)
-        } else {
-            None
+        self.remove_solid_handle(solid);
+        Some(solid)
+    }
+
+    /// Removes the solid. Returns `false` if the handle was already stale.
+    pub fn remove_solid_handle(&mut self, solid: Solid) -> bool {
+        let Some(collider) = self.solids.remove(solid) else {
+            return false;
+        };
+        self.solid_grid.remove(solid, collider.rect());
+
+        // Actors squished by the removed solid are no longer squished by it
+        for actor in self.squished_by.remove(&solid).unwrap_or_default() {
+            if let Some(collider) = self.actors.get_mut(actor) {
+                collider.squishers.remove(&solid);
+                if collider.squishers.is_empty() {
+                    collider.squished = false;
+                }
+            }
         }
+
+        true
+    }
+
+    /// Non-collidable solids stay in the world but are ignored by every
+    /// collision query, e.g. an open door.
+    pub fn set_solid_collidable(&mut self, solid: Solid, collidable: bool) {
+        self.solids[solid].collidable = collidable;
+    }
+
+    pub fn solid_collidable(&self, solid: Solid) -> bool {
+        self.solids[solid].collidable
+    }
+
+    pub fn set_solid_category(&mut self, solid: Solid, category: CollisionLayers) {
+        self.solids[solid].category = category;
+    }
+
+    pub fn solid_category(&self, solid: Solid) -> CollisionLayers {
+        self.solids[solid].category
+    }
+
+    /// All solids overlapping `rect`, collidable or not.
+    pub fn solids_in_rect(&self, rect: Rect) -> Vec<Solid> {
+        self.solid_grid
+            .query(rect)
+            .into_iter()
+            .filter(|&solid| overlaps(&self.solids[solid].rect(), &rect))
+            .collect()
+    }
+
+    /// All actors overlapping `rect`, collidable or not.
+    pub fn actors_in_rect(&self, rect: Rect) -> Vec<Actor> {
+        self.actor_grid
+            .query(rect)
+            .into_iter()
+            .filter(|&actor| overlaps(&self.actors[actor].rect(), &rect))
+            .collect()
+    }
+
+    pub fn contains_solid(&self, solid: Solid) -> bool {
+        self.solids.contains(solid)
     }
 
     pub fn set_actor_size(&mut self, actor: Actor, width: i32, height: i32) {
-        let collider = &mut self.actors[actor.0].1;
+        let collider = &mut self.actors[actor];
+        let old_rect = collider.rect();
 
         let height_diff = collider.height - height;
         let width_diff = collider.width - width;
@@ -206,24 +696,46 @@ impl World {
         collider.x_remainder = 0.0;
         collider.y_remainder = 0.0;
         collider.pos = pos;
+
+        let new_rect = collider.rect();
+        self.actor_grid.update(actor, old_rect, new_rect);
     }
 
     pub fn set_actor_position(&mut self, actor: Actor, pos: Vec2) {
-        let collider = &mut self.actors[actor.0].1;
+        let collider = &mut self.actors[actor];
+        let old_rect = collider.rect();
 
         collider.x_remainder = 0.0;
         collider.y_remainder = 0.0;
         collider.pos = pos;
+
+        let new_rect = collider.rect();
+        self.actor_grid.update(actor, old_rect, new_rect);
+    }
+
+    // Writes back a collider moved by `move_h`/`move_v`, keeping the broadphase in sync
+    fn store_actor(&mut self, actor: Actor, collider: Collider) {
+        let old_rect = self.actors[actor].rect();
+        self.actor_grid.update(actor, old_rect, collider.rect());
+        self.actors[actor] = collider;
     }
 
     pub fn descent(&mut self, actor: Actor) {
-        let collider = &mut self.actors[actor.0].1;
+        let collider = &mut self.actors[actor];
         collider.descent = true;
     }
 
+    /// Top-down movement. X and Y are resolved separately, so an actor blocked
+    /// on one axis keeps sliding along the other.
+    pub fn move_actor(&mut self, actor: Actor, delta: Vec2) -> MoveResult {
+        MoveResult {
+            blocked_x: !self.move_h(actor, delta.x),
+            blocked_y: !self.move_v(actor, delta.y),
+        }
+    }
+
     pub fn move_v(&mut self, actor: Actor, dy: f32) -> bool {
-        let id = actor.0;
-        let mut collider = self.actors[id].1.clone();
+        let mut collider = self.actors[actor].clone();
 
         collider.y_remainder += dy;
 
@@ -234,6 +746,7 @@ impl World {
 
             while move_ != 0 {
                 let tile = self.collide_solids(
+                    collider.mask,
                     collider.pos + vec2(0., sign as f32),
                     collider.width,
                     collider.height,
@@ -252,7 +765,7 @@ impl World {
                     collider.pos.y += sign as f32;
                     move_ -= sign;
                 } else {
-                    self.actors[id].1 = collider;
+                    self.store_actor(actor, collider);
 
                     return false;
                 }
@@ -260,19 +773,19 @@ impl World {
         }
 
         // Final check, if we are out of woods after the move - reset wood flags
-        let tile = self.collide_solids(collider.pos, collider.width, collider.height);
+        let tile =
+            self.collide_solids(collider.mask, collider.pos, collider.width, collider.height);
         if tile != Tile::JumpThrough {
             collider.seen_wood = false;
             collider.descent = false;
         }
 
-        self.actors[id].1 = collider;
+        self.store_actor(actor, collider);
         true
     }
 
     pub fn move_h(&mut self, actor: Actor, dx: f32) -> bool {
-        let id = actor.0;
-        let mut collider = self.actors[id].1.clone();
+        let mut collider = self.actors[actor].clone();
         collider.x_remainder += dx;
 
         let mut move_ = collider.x_remainder.round() as i32;
@@ -282,6 +795,7 @@ impl World {
 
             while move_ != 0 {
                 let tile = self.collide_solids(
+                    collider.mask,
                     collider.pos + vec2(sign as f32, 0.),
                     collider.width,
                     collider.height,
@@ -294,17 +808,17 @@ impl World {
                     collider.pos.x += sign as f32;
                     move_ -= sign;
                 } else {
-                    self.actors[id].1 = collider;
+                    self.store_actor(actor, collider);
                     return false;
                 }
             }
         }
-        self.actors[id].1 = collider;
+        self.store_actor(actor, collider);
         true
     }
 
     pub fn solid_move(&mut self, solid: Solid, dx: f32, dy: f32) {
-        let collider = &mut self.solids[solid.0].1;
+        let collider = &mut self.solids[solid];
 
         collider.x_remainder += dx;
         collider.y_remainder += dy;
@@ -327,7 +841,11 @@ impl World {
             collider.height as f32,
         );
 
-        for (actor, actor_collider) in &mut self.actors {
+        for actor in self
+            .actor_grid
+            .query(riding_rect.combine_with(pushing_rect))
+        {
+            let actor_collider = &self.actors[actor];
             let rider_rect = Rect::new(
                 actor_collider.pos.x,
                 actor_collider.pos.y + actor_collider.height as f32 - 1.0,
@@ -335,36 +853,57 @@ impl World {
                 1.0,
             );
 
-            if riding_rect.overlaps(&rider_rect) {
-                riding_actors.push(*actor);
-            } else if pushing_rect.overlaps(&actor_collider.rect())
-                && actor_collider.squished == false
+            let actor_rect = actor_collider.rect();
+            if overlaps(&riding_rect, &rider_rect) {
+                riding_actors.push(actor);
+            } else if move_x != 0
+                && overlaps(&pushing_rect, &actor_rect)
+                && !actor_collider.squished
             {
-                pushing_actors.push(*actor);
+                // Just far enough to clear the solid's new position
+                let push = if move_x > 0 {
+                    pushing_rect.right() - actor_rect.left()
+                } else {
+                    pushing_rect.left() - actor_rect.right()
+                };
+                pushing_actors.push((actor, push));
             }
+        }
 
-            if pushing_rect.overlaps(&actor_collider.rect()) == false {
+        // Release the actors this solid no longer overlaps
+        if let Some(victims) = self.squished_by.get_mut(&solid) {
+            victims.retain(|&actor| {
+                let actor_collider = &mut self.actors[actor];
+                if overlaps(&pushing_rect, &actor_collider.rect()) {
+                    return true;
+                }
                 actor_collider.squishers.remove(&solid);
-                if actor_collider.squishers.len() == 0 {
+                if actor_collider.squishers.is_empty() {
                     actor_collider.squished = false;
                 }
-            }
+                false
+            });
         }
 
-        self.solids[solid.0].1.collidable = false;
-        for actor in riding_actors {
-            self.move_h(actor, move_x as f32);
-        }
-        for actor in pushing_actors {
-            let squished = !self.move_h(actor, move_x as f32);
-            if squished {
-                self.actors[actor.0].1.squished = true;
-                self.actors[actor.0].1.squishers.insert(solid);
+        // A non-collidable solid passes through actors instead of carrying them
+        if self.solids[solid].collidable {
+            self.solids[solid].collidable = false;
+            for actor in riding_actors {
+                self.move_h(actor, move_x as f32);
+            }
+            for (actor, push) in pushing_actors {
+                let squished = !self.move_h(actor, push);
+                if squished {
+                    self.actors[actor].squished = true;
+                    self.actors[actor].squishers.insert(solid);
+                    self.squished_by.entry(solid).or_default().insert(actor);
+                }
             }
+            self.solids[solid].collidable = true;
         }
-        self.solids[solid.0].1.collidable = true;
 
-        let collider = &mut self.solids[solid.0].1;
+        let collider = &mut self.solids[solid];
+        let old_rect = collider.rect();
         if move_x != 0 {
             collider.x_remainder -= move_x as f32;
             collider.pos.x += move_x as f32;
@@ -373,148 +912,104 @@ impl World {
             collider.y_remainder -= move_y as f32;
             collider.pos.y += move_y as f32;
         }
+        let new_rect = collider.rect();
+        self.solid_grid.update(solid, old_rect, new_rect);
     }
 
     pub fn solid_at(&self, pos: Vec2) -> bool {
-        self.tag_at(pos, 1)
+        self.tag_at(pos, CollisionLayers::SOLID)
     }
 
-    pub fn tag_at(&self, pos: Vec2, tag: u8) -> bool {
-        for StaticTiledLayer {
-            tile_width,
-            tile_height,
-            width,
-            static_colliders,
-            tag: layer_tag,
-        } in &self.static_tiled_layers
+    /// Whether a tile layer or collidable solid in any of the `mask` layers
+    /// covers `pos`.
+    pub fn tag_at(&self, pos: Vec2, mask: CollisionLayers) -> bool {
+        if self
+            .static_tiled_layers
+            .iter()
+            .any(|(_, layer)| layer.category.intersects(mask) && layer.tile_at(pos) != Tile::Empty)
         {
-            let y = (pos.y / tile_width) as i32;
-            let x = (pos.x / tile_height) as i32;
-            let ix = y * (*width as i32) + x;
-
-            if ix >= 0
-                && ix < static_colliders.len() as i32
-                && static_colliders[ix as usize] != Tile::Empty
-            {
-                return *layer_tag == tag;
-            }
+            return true;
         }
 
-        self.solids
-            .iter()
-            .any(|solid| solid.1.collidable && solid.1.rect().contains(pos))
+        self.solid_grid
+            .query(Rect::new(pos.x, pos.y, 0.0, 0.0))
+            .into_iter()
+            .any(|solid| {
+                let solid = &self.solids[solid];
+                solid.collidable && solid.category.intersects(mask) && solid.rect().contains(pos)
+            })
     }
 
-    pub fn collide_solids(&self, pos: Vec2, width: i32, height: i32) -> Tile {
-        let tile = self.collide_tag(1, pos, width, height);
+    /// What a `width`x`height` box at `pos` overlaps in the `mask` layers.
+    pub fn collide_solids(
+        &self,
+        mask: CollisionLayers,
+        pos: Vec2,
+        width: i32,
+        height: i32,
+    ) -> Tile {
+        let tile = self.collide_tag(mask, pos, width, height);
         if tile != Tile::Empty {
             return tile;
         }
 
-        self.solids
-            .iter()
-            .find(|solid| {
-                solid.1.collidable
-                    && solid.1.rect().overlaps(&Rect::new(
-                        pos.x,
-                        pos.y,
-                        width as f32,
-                        height as f32,
-                    ))
+        let rect = Rect::new(pos.x, pos.y, width as f32, height as f32);
+        self.solid_grid
+            .query(rect)
+            .into_iter()
+            .find(|&solid| {
+                let solid = &self.solids[solid];
+                solid.collidable
+                    && solid.category.intersects(mask)
+                    && overlaps(&solid.rect(), &rect)
             })
             .map_or(Tile::Empty, |_| Tile::Collider)
     }
 
-    pub fn collide_tag(&self, tag: u8, pos: Vec2, width: i32, height: i32) -> Tile {
-        for StaticTiledLayer {
-            tile_width,
-            tile_height,
-            width: layer_width,
-            static_colliders,
-            tag: layer_tag,
-        } in &self.static_tiled_layers
-        {
-            let layer_height = static_colliders.len() / layer_width + 1;
-            let check = |pos: Vec2| {
-                let y = (pos.y / tile_width) as i32;
-                let x = (pos.x / tile_height) as i32;
-                let ix = y * (*layer_width as i32) + x;
-                if y >= 0
-                    && y < layer_height as i32
-                    && x >= 0
-                    && x < *layer_width as i32
-                    && ix >= 0
-                    && ix < static_colliders.len() as i32
-                    && *layer_tag == tag
-                    && static_colliders[ix as usize] != Tile::Empty
-                {
-                    return static_colliders[ix as usize];
-                }
-                return Tile::Empty;
-            };
-
-            let tile = check(pos)
-                .or(check(pos + vec2(width as f32 - 1.0, 0.0)))
-                .or(check(pos + vec2(width as f32 - 1.0, height as f32 - 1.0)))
-                .or(check(pos + vec2(0.0, height as f32 - 1.0)));
-
-            if tile != Tile::Empty {
-                return tile;
+    pub fn collide_tag(&self, mask: CollisionLayers, pos: Vec2, width: i32, height: i32) -> Tile {
+        for (_, layer) in self.static_tiled_layers.iter() {
+            if !layer.category.intersects(mask) {
+                continue;
             }
 
-            if width > *tile_width as i32 {
-                let mut x = pos.x;
-
-                while {
-                    x += tile_width;
-                    x < pos.x + width as f32 - 1.
-                } {
-                    let tile =
-                        check(vec2(x, pos.y)).or(check(vec2(x, pos.y + height as f32 - 1.0)));
-                    if tile != Tile::Empty {
-                        return tile;
-                    }
-                }
-            }
+            // Every cell touched by the box, not only the ones under its corners
+            let rect = Rect::new(pos.x, pos.y, width as f32, height as f32);
+            let (columns, rows) = layer.cells_touching(rect);
 
-            if height > *tile_height as i32 {
-                let mut y = pos.y;
-
-                while {
-                    y += tile_height;
-                    y < pos.y + height as f32 - 1.
-                } {
-                    let tile = check(vec2(pos.x, y)).or(check(vec2(pos.x + width as f32 - 1., y)));
-                    if tile != Tile::Empty {
-                        return tile;
-                    }
+            let mut tile = Tile::Empty;
+            for y in rows {
+                for x in columns.clone() {
+                    tile = tile.or(layer.tile_overlapping(x, y, &rect));
                 }
             }
+            if tile != Tile::Empty {
+                return tile;
+            }
         }
-        return Tile::Empty;
+        Tile::Empty
     }
 
     pub fn squished(&self, actor: Actor) -> bool {
-        self.actors[actor.0].1.squished
+        self.actors[actor].squished
     }
 
     pub fn actor_pos(&self, actor: Actor) -> Vec2 {
-        self.actors[actor.0].1.pos
+        self.actors[actor].pos
     }
 
     pub fn actor_size(&self, actor: Actor) -> (i32, i32) {
-        (self.actors[actor.0].1.width, self.actors[actor.0].1.height)
+        (self.actors[actor].width, self.actors[actor].height)
     }
 
     pub fn solid_pos(&self, solid: Solid) -> Vec2 {
-        self.solids[solid.0].1.pos
+        self.solids[solid].pos
     }
 
     pub fn collide_check(&self, collider: Actor, pos: Vec2) -> bool {
-        let collider = &self.actors[collider.0];
+        let collider = &self.actors[collider];
 
-        let tile = self.collide_solids(pos, collider.1.width, collider.1.height);
-        if collider.1.descent {
+        let tile = self.collide_solids(collider.mask, pos, collider.width, collider.height);
+        if collider.descent {
             tile == Tile::Solid || tile == Tile::Collider
         } else {
             tile == Tile::Solid || tile == Tile::Collider || tile == Tile::JumpThrough
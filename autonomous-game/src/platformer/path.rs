@@ -0,0 +1,190 @@
+use macroquad::math::{vec2, Rect, Vec2};
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::{Actor, World};
+
+/// Spacing of the pathfinding grid, in pixels. Half a tile, so an actor as
+/// wide as a tile still finds doorways as wide as a tile.
+const PATH_CELL: f32 = 8.0;
+/// How far a blocked grid point slides along either axis to get clear, so
+/// gaps exactly as wide as the box are found when they're off the grid.
+const SNAP: i32 = PATH_CELL as i32 / 2;
+// Cost of a straight and a diagonal step, about 1 and √2 cells
+const STRAIGHT: u32 = 10;
+const DIAGONAL: u32 = 14;
+
+type Node = (i32, i32);
+
+impl World {
+    /// Where `actor` has to walk, leg by leg in straight lines, to get its
+    /// box from where it is to `to`, both top left corners. The last point is
+    /// `to`. Tile layers and collidable solids in the actor's mask block the
+    /// way, other actors don't, and the box never leaves `bounds`.
+    ///
+    /// `None` if the box doesn't fit at `to` or can't get there.
+    pub fn find_path(&self, actor: Actor, to: Vec2, bounds: Rect) -> Option<Vec<Vec2>> {
+        let from = self.actors[actor].pos;
+        let (width, height) = self.actor_size(actor);
+        let inside = |pos: Vec2| {
+            pos.x >= bounds.left()
+                && pos.y >= bounds.top()
+                && pos.x + width as f32 <= bounds.right()
+                && pos.y + height as f32 <= bounds.bottom()
+        };
+        if !inside(to) || self.collide_check(actor, to) {
+            return None;
+        }
+
+        // Where the box stands for each node, its grid point or the closest
+        // clear spot within `SNAP` of it along one axis
+        let mut spots: HashMap<Node, Option<Vec2>> = HashMap::new();
+        let mut spot = |node: Node| {
+            *spots.entry(node).or_insert_with(|| {
+                let pos = position(node);
+                std::iter::once(pos)
+                    .chain(
+                        (1..=SNAP)
+                            .flat_map(|d| [(d, 0), (-d, 0), (0, d), (0, -d)])
+                            .map(|(dx, dy)| pos + vec2(dx as f32, dy as f32)),
+                    )
+                    .find(|&pos| inside(pos) && !self.collide_check(actor, pos))
+            })
+        };
+        // Whether the box can walk straight from `a` to `b`, touching what's
+        // there when it arrives is fine
+        let clear = |a: Vec2, b: Vec2| {
+            self.sweep_box_from(actor, a, b - a)
+                .is_none_or(|hit| hit.distance >= a.distance(b) - 1e-3)
+        };
+        // How the box gets from `a` to `b`, straight or along one axis then
+        // the other round the corner given. `None` if neither is clear.
+        let via = |a: Vec2, b: Vec2| {
+            if clear(a, b) {
+                return Some(None);
+            }
+            [vec2(b.x, a.y), vec2(a.x, b.y)]
+                .into_iter()
+                .find(|&corner| clear(a, corner) && clear(corner, b))
+                .map(Some)
+        };
+        // Grid points around a position the box can walk to or from
+        let mut corners = |pos: Vec2| -> Vec<Node> {
+            let (x, y) = ((pos.x / PATH_CELL).floor(), (pos.y / PATH_CELL).floor());
+            [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .into_iter()
+                .map(|(dx, dy)| (x as i32 + dx, y as i32 + dy))
+                .filter(|&node| spot(node).is_some_and(|at| via(pos, at).is_some()))
+                .collect()
+        };
+        let starts = corners(from);
+        let goals = corners(to);
+        if starts.is_empty() || goals.is_empty() {
+            return None;
+        }
+
+        // A* from every start to the closest goal
+        let heuristic = |(x, y): Node| {
+            goals
+                .iter()
+                .map(|&(gx, gy)| {
+                    let (dx, dy) = ((gx - x).unsigned_abs(), (gy - y).unsigned_abs());
+                    DIAGONAL * dx.min(dy) + STRAIGHT * dx.abs_diff(dy)
+                })
+                .min()
+                .unwrap_or(u32::MAX)
+        };
+        let mut open = BinaryHeap::new();
+        let mut cost: HashMap<Node, u32> = HashMap::new();
+        let mut came_from: HashMap<Node, Node> = HashMap::new();
+        for &start in &starts {
+            cost.insert(start, 0);
+            open.push(Reverse((heuristic(start), start)));
+        }
+
+        let mut reached = None;
+        while let Some(Reverse((_, node))) = open.pop() {
+            if goals.contains(&node) {
+                reached = Some(node);
+                break;
+            }
+            let (x, y) = node;
+            let Some(here) = spot(node) else {
+                continue;
+            };
+            for (dx, dy) in [
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ] {
+                let next = (x + dx, y + dy);
+                let Some(there) = spot(next) else {
+                    continue;
+                };
+                // Between grid points the box sweeps over nothing the two ends
+                // don't cover, as long as diagonals don't cut corners past a
+                // wall. Slid spots need sweeps of their own.
+                let diagonal = dx != 0 && dy != 0;
+                let covered = here == position(node)
+                    && there == position(next)
+                    && (!diagonal
+                        || [(x + dx, y), (x, y + dy)]
+                            .into_iter()
+                            .all(|side| spot(side) == Some(position(side))));
+                if !covered && via(here, there).is_none() {
+                    continue;
+                }
+                let step = if diagonal { DIAGONAL } else { STRAIGHT };
+                let next_cost = cost[&node] + step;
+                if cost.get(&next).is_none_or(|&known| next_cost < known) {
+                    cost.insert(next, next_cost);
+                    came_from.insert(next, node);
+                    open.push(Reverse((next_cost.saturating_add(heuristic(next)), next)));
+                }
+            }
+        }
+
+        let mut node = reached?;
+        // every node reached has a spot
+        let spot = |node: Node| spots[&node].unwrap();
+        let mut points = vec![to, spot(node)];
+        while let Some(&previous) = came_from.get(&node) {
+            points.push(spot(previous));
+            node = previous;
+        }
+        points.push(from);
+        points.reverse();
+        // with the corners round which slid spots are reached
+        let points: Vec<Vec2> = std::iter::once(from)
+            .chain(
+                points
+                    .windows(2)
+                    .flat_map(|pair| via(pair[0], pair[1]).flatten().into_iter().chain([pair[1]])),
+            )
+            .collect();
+
+        // Skip every point the box can walk past in a straight line
+        let mut path = vec![];
+        let mut current = 0;
+        while current < points.len() - 1 {
+            let mut next = current + 1;
+            while next + 1 < points.len() && clear(points[current], points[next + 1]) {
+                next += 1;
+            }
+            path.push(points[next]);
+            current = next;
+        }
+        path.dedup();
+        Some(path)
+    }
+}
+
+fn position((x, y): Node) -> Vec2 {
+    vec2(x as f32, y as f32) * PATH_CELL
+}
@@ -0,0 +1,333 @@
+use macroquad::math::{vec2, Rect, Vec2};
+
+use super::{Actor, CollisionLayers, Solid, StaticTiledLayer, Tile, World};
+
+/// Result of `World::raycast` and `World::sweep_box`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Where the ray hit, or the position of the swept box at the moment of contact
+    pub point: Vec2,
+    /// Normal of the face that was hit, zero if the ray started inside a tile
+    pub normal: Vec2,
+    /// Distance travelled before the hit
+    pub distance: f32,
+    /// Static tile that was hit, `Tile::Collider` for solids and actors
+    pub tile: Tile,
+    pub solid: Option<Solid>,
+    pub actor: Option<Actor>,
+}
+
+impl World {
+    /// Casts a ray against `SOLID` tiles and solids and against actors.
+    pub fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<Hit> {
+        self.raycast_layers(
+            origin,
+            dir,
+            max_dist,
+            CollisionLayers::SOLID | CollisionLayers::ACTOR,
+        )
+    }
+
+    /// Casts a ray against tile layers, collidable solids and actors in the
+    /// `mask` layers. Solids and actors containing `origin` are ignored, so an
+    /// actor can cast from inside its own collider.
+    pub fn raycast_layers(
+        &self,
+        origin: Vec2,
+        dir: Vec2,
+        max_dist: f32,
+        mask: CollisionLayers,
+    ) -> Option<Hit> {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec2::ZERO {
+            return None;
+        }
+
+        let mut best: Option<Hit> = None;
+        let mut consider = |hit: Hit| {
+            if hit.distance <= max_dist && best.is_none_or(|best| hit.distance < best.distance) {
+                best = Some(hit);
+            }
+        };
+
+        for (_, layer) in self
+            .static_tiled_layers
+            .iter()
+            .filter(|(_, layer)| layer.category.intersects(mask))
+        {
+            if let Some((distance, normal, tile)) = layer.raycast(origin, dir, max_dist) {
+                consider(Hit {
+                    point: origin + dir * distance,
+                    normal,
+                    distance,
+                    tile,
+                    solid: None,
+                    actor: None,
+                });
+            }
+        }
+
+        let end = origin + dir * max_dist;
+        let bounds = Rect::new(
+            origin.x.min(end.x),
+            origin.y.min(end.y),
+            (end.x - origin.x).abs(),
+            (end.y - origin.y).abs(),
+        );
+        for solid in self.solid_grid.query(bounds) {
+            let collider = &self.solids[solid];
+            if !collider.collidable || !collider.category.intersects(mask) {
+                continue;
+            }
+            if let Some((distance, normal)) = ray_rect(origin, dir, collider.rect()) {
+                consider(Hit {
+                    point: origin + dir * distance,
+                    normal,
+                    distance,
+                    tile: Tile::Collider,
+                    solid: Some(solid),
+                    actor: None,
+                });
+            }
+        }
+        for actor in self.actor_grid.query(bounds) {
+            let collider = &self.actors[actor];
+            if !collider.collidable || !collider.category.intersects(mask) {
+                continue;
+            }
+            if let Some((distance, normal)) = ray_rect(origin, dir, collider.rect()) {
+                consider(Hit {
+                    point: origin + dir * distance,
+                    normal,
+                    distance,
+                    tile: Tile::Collider,
+                    solid: None,
+                    actor: Some(actor),
+                });
+            }
+        }
+
+        best
+    }
+
+    /// Moves the actor's box along `delta` without changing the world and
+    /// reports the first thing in its mask it would run into. Obstacles the
+    /// box already overlaps are ignored.
+    pub fn sweep_box(&self, actor: Actor, delta: Vec2) -> Option<Hit> {
+        self.sweep_box_from(actor, self.actors[actor].pos, delta)
+    }
+
+    /// Like `sweep_box`, with the actor's box starting at `start` instead of
+    /// where the actor is.
+    pub fn sweep_box_from(&self, actor: Actor, start: Vec2, delta: Vec2) -> Option<Hit> {
+        let collider = &self.actors[actor];
+        let mask = collider.mask;
+        let size = vec2(collider.width as f32, collider.height as f32);
+
+        let max_dist = delta.length();
+        if max_dist == 0.0 {
+            return None;
+        }
+        let dir = delta / max_dist;
+
+        // Sweeping a box is a ray cast from its corner against obstacles grown by its size
+        let mut best: Option<Hit> = None;
+        let mut consider =
+            |hit: Option<(f32, Vec2)>, tile: Tile, solid: Option<Solid>, actor: Option<Actor>| {
+                if let Some((distance, normal)) = hit {
+                    if distance <= max_dist && best.is_none_or(|best| distance < best.distance) {
+                        best = Some(Hit {
+                            point: start + dir * distance,
+                            normal,
+                            distance,
+                            tile,
+                            solid,
+                            actor,
+                        });
+                    }
+                }
+            };
+        let grown = |rect: Rect| {
+            let grown = Rect::new(
+                rect.x - size.x,
+                rect.y - size.y,
+                rect.w + size.x,
+                rect.h + size.y,
+            );
+            ray_rect(start, dir, grown)
+        };
+
+        let end = start + delta;
+        let swept = Rect::new(
+            start.x.min(end.x),
+            start.y.min(end.y),
+            size.x + delta.x.abs(),
+            size.y + delta.y.abs(),
+        );
+
+        for (_, layer) in self
+            .static_tiled_layers
+            .iter()
+            .filter(|(_, layer)| layer.category.intersects(mask))
+        {
+            let (columns, rows) = layer.cells_touching(swept);
+            for y in rows {
+                for x in columns.clone() {
+                    let tile = layer.tile(x, y);
+                    if tile == Tile::Empty {
+                        continue;
+                    }
+                    match layer.cell_shapes(x, y) {
+                        Some(shapes) => {
+                            for shape in shapes {
+                                consider(shape.sweep(size, start, dir), tile, None, None);
+                            }
+                        }
+                        None => consider(grown(layer.tile_rect(x, y)), tile, None, None),
+                    }
+                }
+            }
+        }
+        for solid in self.solid_grid.query(swept) {
+            let collider = &self.solids[solid];
+            if collider.collidable && collider.category.intersects(mask) {
+                consider(grown(collider.rect()), Tile::Collider, Some(solid), None);
+            }
+        }
+        for other in self.actor_grid.query(swept) {
+            let collider = &self.actors[other];
+            if other != actor && collider.collidable && collider.category.intersects(mask) {
+                consider(grown(collider.rect()), Tile::Collider, None, Some(other));
+            }
+        }
+
+        best
+    }
+}
+
+impl StaticTiledLayer {
+    // Walks the grid cell by cell along the ray (Amanatides & Woo)
+    fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<(f32, Vec2, Tile)> {
+        let (mut x, mut y) = self.cell(origin);
+        let world_origin = origin;
+        // distances don't depend on the offset, so walk in layer space
+        let origin = origin - self.offset;
+        let step_x = dir.x.signum() as i32 * (dir.x != 0.0) as i32;
+        let step_y = dir.y.signum() as i32 * (dir.y != 0.0) as i32;
+
+        let t_delta_x = (self.tile_width / dir.x).abs();
+        let t_delta_y = (self.tile_height / dir.y).abs();
+        let mut t_max_x = match step_x {
+            1 => ((x + 1) as f32 * self.tile_width - origin.x) / dir.x,
+            -1 => (x as f32 * self.tile_width - origin.x) / dir.x,
+            _ => f32::INFINITY,
+        };
+        let mut t_max_y = match step_y {
+            1 => ((y + 1) as f32 * self.tile_height - origin.y) / dir.y,
+            -1 => (y as f32 * self.tile_height - origin.y) / dir.y,
+            _ => f32::INFINITY,
+        };
+
+        let (width, height) = (self.width as i32, self.height() as i32);
+        let mut distance = 0.0;
+        let mut normal = Vec2::ZERO;
+        loop {
+            let tile = self.tile(x, y);
+            if tile != Tile::Empty && self.cell_shapes(x, y).is_none() {
+                return Some((distance, normal, tile));
+            }
+            // Partial tiles only stop the ray if one of their shapes is in
+            // the way. Shapes can stick out of their cell, the ones hit in
+            // this cell are the first ones along the ray.
+            let cell = self.tile_rect(x, y);
+            let cell = Rect::new(cell.x - 1e-3, cell.y - 1e-3, cell.w + 2e-3, cell.h + 2e-3);
+            let hit = self
+                .shapes_near(x, y)
+                .filter_map(|(tile, shape)| {
+                    if shape.contains(world_origin) {
+                        Some((0.0, Vec2::ZERO, tile))
+                    } else {
+                        let (distance, normal) = shape.ray(world_origin, dir)?;
+                        cell.contains(world_origin + dir * distance)
+                            .then_some((distance, normal, tile))
+                    }
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if hit.is_some() {
+                return hit;
+            }
+
+            // Nothing left to hit once the ray leaves the layer for good
+            let reach = self.reach;
+            if (x < -reach && step_x <= 0)
+                || (x >= width + reach && step_x >= 0)
+                || (y < -reach && step_y <= 0)
+                || (y >= height + reach && step_y >= 0)
+            {
+                return None;
+            }
+
+            if t_max_x < t_max_y {
+                distance = t_max_x;
+                x += step_x;
+                t_max_x += t_delta_x;
+                normal = vec2(-step_x as f32, 0.0);
+            } else {
+                distance = t_max_y;
+                y += step_y;
+                t_max_y += t_delta_y;
+                normal = vec2(0.0, -step_y as f32);
+            }
+
+            if distance > max_dist {
+                return None;
+            }
+        }
+    }
+}
+
+/// Distance and normal where the ray `origin + dir * t` enters `rect`. Rays
+/// starting inside the rect or only grazing its edge miss.
+fn ray_rect(origin: Vec2, dir: Vec2, rect: Rect) -> Option<(f32, Vec2)> {
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+
+    for (o, d, min, max, face) in [
+        (
+            origin.x,
+            dir.x,
+            rect.left(),
+            rect.right(),
+            vec2(-dir.x.signum(), 0.0),
+        ),
+        (
+            origin.y,
+            dir.y,
+            rect.top(),
+            rect.bottom(),
+            vec2(0.0, -dir.y.signum()),
+        ),
+    ] {
+        if d == 0.0 {
+            if o <= min || o >= max {
+                return None;
+            }
+            continue;
+        }
+
+        let t0 = (min - o) / d;
+        let t1 = (max - o) / d;
+        let (t_enter, t_exit) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+        if t_enter > t_near {
+            t_near = t_enter;
+            normal = face;
+        }
+        t_far = t_far.min(t_exit);
+    }
+
+    if t_near < 0.0 || t_near >= t_far {
+        return None;
+    }
+    Some((t_near, normal))
+}
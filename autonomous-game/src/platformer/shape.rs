@@ -0,0 +1,170 @@
+use macroquad::math::{vec2, Rect, Vec2};
+
+/// Collision shape of a single tile, relative to the tile's top left corner.
+/// Tiles without shapes block their whole cell.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TileShape {
+    Rect(Rect),
+    /// Convex polygon such as a diagonal slope, in either winding order
+    Polygon(Vec<Vec2>),
+}
+
+/// A `TileShape` placed in the world, as vertices and outward edge normals.
+#[derive(Clone, Debug)]
+pub(super) struct Convex {
+    vertices: Vec<Vec2>,
+    normals: Vec<Vec2>,
+}
+
+const AXES: [Vec2; 4] = [
+    vec2(1.0, 0.0),
+    vec2(-1.0, 0.0),
+    vec2(0.0, 1.0),
+    vec2(0.0, -1.0),
+];
+
+impl Convex {
+    /// `None` for shapes without area, they can't block anything.
+    pub(super) fn new(shape: &TileShape, origin: Vec2) -> Option<Convex> {
+        let vertices: Vec<Vec2> = match shape {
+            TileShape::Rect(rect) => vec![
+                vec2(rect.left(), rect.top()),
+                vec2(rect.right(), rect.top()),
+                vec2(rect.right(), rect.bottom()),
+                vec2(rect.left(), rect.bottom()),
+            ],
+            TileShape::Polygon(points) => points.clone(),
+        }
+        .into_iter()
+        .map(|vertex| origin + vertex)
+        .collect();
+
+        // shoelace formula, twice the signed area
+        let area: f32 = (0..vertices.len())
+            .map(|i| {
+                let next = vertices[(i + 1) % vertices.len()];
+                (vertices[i] - origin).perp_dot(next - origin)
+            })
+            .sum();
+        if area.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let mut normals = vec![];
+        for (i, &a) in vertices.iter().enumerate() {
+            let b = vertices[(i + 1) % vertices.len()];
+            // the outward side depends on the winding
+            let normal = (vec2(b.y - a.y, a.x - b.x) * area.signum()).normalize_or_zero();
+            if normal != Vec2::ZERO {
+                normals.push(normal);
+            }
+        }
+        Some(Convex { vertices, normals })
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        self.vertices
+            .iter()
+            .map(|vertex| vertex.dot(axis))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+                (min.min(d), max.max(d))
+            })
+    }
+
+    /// Whether the interiors intersect, like `super::overlaps`.
+    pub(super) fn overlaps(&self, rect: &Rect) -> bool {
+        let corners = [
+            vec2(rect.left(), rect.top()),
+            vec2(rect.right(), rect.top()),
+            vec2(rect.right(), rect.bottom()),
+            vec2(rect.left(), rect.bottom()),
+        ];
+        // separating axis test, edges of both shapes are the only candidates
+        [AXES[0], AXES[2]].iter().chain(&self.normals).all(|&axis| {
+            let (min, max) = self.project(axis);
+            let (rect_min, rect_max) = corners
+                .iter()
+                .map(|corner| corner.dot(axis))
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+                    (min.min(d), max.max(d))
+                });
+            max > rect_min && rect_max > min
+        })
+    }
+
+    /// Smallest rect around the shape.
+    pub(super) fn bounds(&self) -> Rect {
+        let (left, right) = self.project(AXES[0]);
+        let (top, bottom) = self.project(AXES[2]);
+        Rect::new(left, top, right - left, bottom - top)
+    }
+
+    pub(super) fn contains(&self, point: Vec2) -> bool {
+        self.overlaps(&Rect::new(point.x, point.y, 0.0, 0.0))
+    }
+
+    /// Distance and normal where the ray enters the shape, like `query::ray_rect`.
+    pub(super) fn ray(&self, origin: Vec2, dir: Vec2) -> Option<(f32, Vec2)> {
+        ray_planes(
+            origin,
+            dir,
+            self.normals
+                .iter()
+                .map(|&normal| (normal, self.support(normal))),
+        )
+    }
+
+    /// Like `ray`, for a `size` box whose top left corner moves along the ray.
+    pub(super) fn sweep(&self, size: Vec2, origin: Vec2, dir: Vec2) -> Option<(f32, Vec2)> {
+        // The Minkowski sum with the box has the edges of both shapes
+        ray_planes(
+            origin,
+            dir,
+            self.normals.iter().chain(&AXES).map(|&normal| {
+                let box_support = (-normal.x * size.x).max(0.0) + (-normal.y * size.y).max(0.0);
+                (normal, self.support(normal) + box_support)
+            }),
+        )
+    }
+
+    fn support(&self, normal: Vec2) -> f32 {
+        self.project(normal).1
+    }
+}
+
+// Cyrus-Beck clipping against half planes `normal . p <= offset`
+fn ray_planes(
+    origin: Vec2,
+    dir: Vec2,
+    planes: impl Iterator<Item = (Vec2, f32)>,
+) -> Option<(f32, Vec2)> {
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+
+    for (plane_normal, offset) in planes {
+        let outside = plane_normal.dot(origin) - offset;
+        let approach = plane_normal.dot(dir);
+        if approach == 0.0 {
+            if outside >= 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = -outside / approach;
+        if approach < 0.0 {
+            if t > t_near {
+                t_near = t;
+                normal = plane_normal;
+            }
+        } else {
+            t_far = t_far.min(t);
+        }
+    }
+
+    if t_near < 0.0 || t_near >= t_far {
+        return None;
+    }
+    Some((t_near, normal))
+}
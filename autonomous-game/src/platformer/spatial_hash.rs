@@ -0,0 +1,76 @@
+use macroquad::math::Rect;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Uniform grid broadphase. Every key is stored in each cell its rect touches,
+/// so a query only has to look at the cells covered by the probe rect.
+pub struct SpatialHash<K> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<K>>,
+}
+
+impl<K: Copy + Eq + Hash> SpatialHash<K> {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, rect: Rect) {
+        for cell in self.cells_of(rect) {
+            self.cells.entry(cell).or_default().push(key);
+        }
+    }
+
+    pub fn remove(&mut self, key: K, rect: Rect) {
+        for cell in self.cells_of(rect) {
+            if let Some(keys) = self.cells.get_mut(&cell) {
+                keys.retain(|k| *k != key);
+                if keys.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// Moves `key` from the cells of `old` to the cells of `new`.
+    pub fn update(&mut self, key: K, old: Rect, new: Rect) {
+        if self.cell_range(old) == self.cell_range(new) {
+            return;
+        }
+        self.remove(key, old);
+        self.insert(key, new);
+    }
+
+    /// Every key stored in a cell touched by `rect`, without duplicates.
+    /// Candidates still need an exact overlap test.
+    pub fn query(&self, rect: Rect) -> Vec<K> {
+        let mut found = vec![];
+        for cell in self.cells_of(rect) {
+            if let Some(keys) = self.cells.get(&cell) {
+                for key in keys {
+                    if !found.contains(key) {
+                        found.push(*key);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn cell_range(&self, rect: Rect) -> (i32, i32, i32, i32) {
+        (
+            (rect.x / self.cell_size).floor() as i32,
+            (rect.y / self.cell_size).floor() as i32,
+            ((rect.x + rect.w) / self.cell_size).floor() as i32,
+            ((rect.y + rect.h) / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cells_of(&self, rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        let (x0, y0, x1, y1) = self.cell_range(rect);
+        (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (x, y)))
+    }
+}
@@ -0,0 +1,752 @@
+use super::*;
+
+use proptest::prelude::*;
+
+/// Builds a static layer from rows of `#` (solid), `-` (jumpthrough) and `.`.
+fn layer(rows: &[&str]) -> (Vec<Tile>, usize) {
+    let width = rows[0].len();
+    let tiles = rows
+        .iter()
+        .flat_map(|row| row.chars())
+        .map(|c| match c {
+            '#' => Tile::Solid,
+            '-' => Tile::JumpThrough,
+            _ => Tile::Empty,
+        })
+        .collect();
+    (tiles, width)
+}
+
+fn world(rows: &[&str], tile_width: f32, tile_height: f32) -> World {
+    let (tiles, width) = layer(rows);
+    let mut world = World::new();
+    world.add_static_tiled_layer(
+        tiles,
+        tile_width,
+        tile_height,
+        width,
+        CollisionLayers::SOLID,
+    );
+    world
+}
+
+fn collides(world: &World, pos: Vec2, width: i32, height: i32) -> bool {
+    world.collide_solids(CollisionLayers::SOLID, pos, width, height) != Tile::Empty
+}
+
+#[test]
+fn collide_tag_checks_every_corner() {
+    let world = world(&["....", ".#..", "....", "...."], 16.0, 16.0);
+
+    assert!(!collides(&world, vec2(0.0, 0.0), 16, 16));
+    // each corner of the box in turn over the solid tile
+    assert!(collides(&world, vec2(1.0, 1.0), 16, 16));
+    assert!(collides(&world, vec2(31.0, 1.0), 16, 16));
+    assert!(collides(&world, vec2(1.0, 31.0), 16, 16));
+    assert!(collides(&world, vec2(31.0, 31.0), 16, 16));
+    // touching the tile edge is not an overlap
+    assert!(!collides(&world, vec2(0.0, 16.0), 16, 16));
+    assert!(!collides(&world, vec2(32.0, 16.0), 16, 16));
+}
+
+#[test]
+fn collide_tag_checks_tiles_inside_large_boxes() {
+    let world = world(&[".....", ".....", "..#..", ".....", "....."], 16.0, 16.0);
+
+    // the solid tile is fully inside the box, away from its edges
+    assert!(collides(&world, vec2(8.0, 8.0), 64, 64));
+}
+
+#[test]
+fn collide_tag_uses_tile_width_for_x_and_tile_height_for_y() {
+    // 16 wide, 32 high: the solid tile covers x 16..32, y 32..64
+    let world = world(&["...", ".#.", "..."], 16.0, 32.0);
+
+    assert!(collides(&world, vec2(20.0, 40.0), 4, 4));
+    assert!(!collides(&world, vec2(40.0, 20.0), 4, 4));
+    assert!(!collides(&world, vec2(20.0, 20.0), 4, 4));
+    assert!(world.solid_at(vec2(20.0, 40.0)));
+    assert!(!world.solid_at(vec2(40.0, 20.0)));
+}
+
+#[test]
+fn collide_tag_ignores_positions_outside_the_layer() {
+    let world = world(&["#..#", "....", "#..."], 16.0, 16.0);
+
+    // past the right edge must not wrap into the next row
+    assert!(!collides(&world, vec2(64.0, 16.0), 16, 16));
+    assert!(!world.solid_at(vec2(70.0, 20.0)));
+    // left of the layer must not read the previous row
+    assert!(!collides(&world, vec2(-17.0, 16.0), 16, 16));
+    assert!(!world.solid_at(vec2(-4.0, 20.0)));
+    // below the last row
+    assert!(!collides(&world, vec2(0.0, 48.0), 16, 16));
+}
+
+#[test]
+fn collide_tag_handles_a_partial_last_row() {
+    let mut world = World::new();
+    let tiles = vec![
+        Tile::Empty,
+        Tile::Empty,
+        Tile::Empty,
+        Tile::Empty,
+        Tile::Empty,
+        Tile::Solid,
+    ];
+    world.add_static_tiled_layer(tiles, 16.0, 16.0, 4, CollisionLayers::SOLID);
+
+    assert!(world.solid_at(vec2(20.0, 20.0)));
+    assert!(!world.solid_at(vec2(40.0, 20.0)));
+}
+
+#[test]
+fn tag_at_checks_every_layer_in_the_mask() {
+    let mut world = World::new();
+    let (tiles, width) = layer(&["#.", ".."]);
+    world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::WATER);
+    let (tiles, width) = layer(&["#.", ".#"]);
+    world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::SOLID);
+
+    // the water layer is non-empty here but must not hide the solid layer below
+    assert!(world.tag_at(vec2(4.0, 4.0), CollisionLayers::SOLID));
+    assert!(world.tag_at(vec2(4.0, 4.0), CollisionLayers::WATER));
+    assert!(world.tag_at(vec2(20.0, 20.0), CollisionLayers::SOLID));
+    assert!(!world.tag_at(vec2(20.0, 20.0), CollisionLayers::WATER));
+    assert!(!world.tag_at(
+        vec2(20.0, 4.0),
+        CollisionLayers::WATER | CollisionLayers::SOLID
+    ));
+}
+
+#[test]
+fn actor_mask_selects_blocking_layers() {
+    let mut world = World::new();
+    let (tiles, width) = layer(&["....", "..#.", "...."]);
+    world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::WATER);
+
+    let walker = world.add_actor(vec2(0.0, 16.0), 16, 16);
+    world.set_actor_mask(walker, CollisionLayers::SOLID | CollisionLayers::WATER);
+    let boat = world.add_actor(vec2(0.0, 16.0), 16, 16);
+
+    assert!(!world.move_h(walker, 32.0));
+    assert_eq!(world.actor_pos(walker), vec2(16.0, 16.0));
+    assert!(world.move_h(boat, 32.0));
+    assert_eq!(world.actor_pos(boat), vec2(32.0, 16.0));
+}
+
+#[test]
+fn move_h_and_move_v_stop_next_to_walls() {
+    let mut world = world(&["#####", "#...#", "#...#", "#####"], 16.0, 16.0);
+    let actor = world.add_actor(vec2(20.0, 20.0), 16, 16);
+
+    assert!(!world.move_h(actor, 100.0));
+    assert_eq!(world.actor_pos(actor), vec2(48.0, 20.0));
+    assert!(!world.move_v(actor, 100.0));
+    assert_eq!(world.actor_pos(actor), vec2(48.0, 32.0));
+    assert!(!world.move_h(actor, -100.0));
+    assert_eq!(world.actor_pos(actor), vec2(16.0, 32.0));
+    assert!(!world.move_v(actor, -100.0));
+    assert_eq!(world.actor_pos(actor), vec2(16.0, 16.0));
+}
+
+#[test]
+fn move_h_accumulates_sub_pixel_movement() {
+    let mut world = world(&["....", "...."], 16.0, 16.0);
+    let actor = world.add_actor(vec2(0.0, 0.0), 8, 8);
+
+    for _ in 0..4 {
+        assert!(world.move_h(actor, 0.3));
+    }
+    // 1.2 pixels requested, one whole pixel moved so far
+    assert_eq!(world.actor_pos(actor), vec2(1.0, 0.0));
+}
+
+#[test]
+fn move_v_passes_jumpthrough_upwards_and_lands_on_it() {
+    let mut world = world(&["....", "----", "....", "...."], 16.0, 16.0);
+    let actor = world.add_actor(vec2(0.0, 40.0), 16, 8);
+
+    // jumping up through the platform
+    assert!(world.move_v(actor, -40.0));
+    assert_eq!(world.actor_pos(actor), vec2(0.0, 0.0));
+    // falling back down lands on top of it
+    assert!(!world.move_v(actor, 40.0));
+    assert_eq!(world.actor_pos(actor), vec2(0.0, 8.0));
+}
+
+#[test]
+fn move_actor_slides_along_walls() {
+    let mut world = world(&["......", "......", "######"], 16.0, 16.0);
+    let actor = world.add_actor(vec2(0.0, 10.0), 16, 16);
+
+    let result = world.move_actor(actor, vec2(20.0, 20.0));
+
+    assert!(!result.blocked_x);
+    assert!(result.blocked_y);
+    assert_eq!(world.actor_pos(actor), vec2(20.0, 16.0));
+}
+
+#[test]
+fn solid_move_carries_riding_actors() {
+    let mut world = World::new();
+    let platform = world.add_solid(vec2(0.0, 32.0), 32, 8);
+    let rider = world.add_actor(vec2(8.0, 16.0), 16, 16);
+    let bystander = world.add_actor(vec2(100.0, 16.0), 16, 16);
+
+    world.solid_move(platform, 10.0, 0.0);
+
+    assert_eq!(world.solid_pos(platform), vec2(10.0, 32.0));
+    assert_eq!(world.actor_pos(rider), vec2(18.0, 16.0));
+    assert_eq!(world.actor_pos(bystander), vec2(100.0, 16.0));
+}
+
+#[test]
+fn actors_slide_along_solids_they_touch() {
+    let mut world = World::new();
+    world.add_solid(vec2(16.0, 0.0), 16, 64);
+    let actor = world.add_actor(vec2(0.0, 0.0), 16, 16);
+
+    assert!(world.move_v(actor, 20.0));
+    assert_eq!(world.actor_pos(actor), vec2(0.0, 20.0));
+    assert!(!world.move_h(actor, 5.0));
+}
+
+#[test]
+fn solid_move_pushes_actors() {
+    let mut world = World::new();
+    let pusher = world.add_solid(vec2(0.0, 0.0), 16, 16);
+    let actor = world.add_actor(vec2(20.0, 0.0), 16, 16);
+
+    world.solid_move(pusher, 10.0, 0.0);
+
+    assert_eq!(world.solid_pos(pusher), vec2(10.0, 0.0));
+    assert_eq!(world.actor_pos(actor), vec2(26.0, 0.0));
+    assert!(!world.squished(actor));
+}
+
+#[test]
+fn solid_move_squishes_actors_against_walls() {
+    let mut world = world(&["...#"], 16.0, 16.0);
+    let pusher = world.add_solid(vec2(0.0, 0.0), 16, 16);
+    let actor = world.add_actor(vec2(16.0, 0.0), 16, 16);
+
+    world.solid_move(pusher, 20.0, 0.0);
+
+    assert!(world.squished(actor));
+    assert_eq!(world.actor_pos(actor), vec2(32.0, 0.0));
+
+    // moving away releases the actor
+    world.solid_move(pusher, -20.0, 0.0);
+    assert!(!world.squished(actor));
+}
+
+#[test]
+fn removing_a_squishing_solid_releases_the_actor() {
+    let mut world = world(&["...#"], 16.0, 16.0);
+    let pusher = world.add_solid(vec2(0.0, 0.0), 16, 16);
+    let actor = world.add_actor(vec2(16.0, 0.0), 16, 16);
+    world.solid_move(pusher, 20.0, 0.0);
+    assert!(world.squished(actor));
+
+    assert!(world.remove_solid_handle(pusher));
+
+    assert!(!world.squished(actor));
+}
+
+#[test]
+fn non_collidable_solids_do_not_block_or_push() {
+    let mut world = World::new();
+    let door = world.add_solid(vec2(16.0, 0.0), 16, 16);
+    let actor = world.add_actor(vec2(0.0, 0.0), 8, 8);
+
+    assert!(collides(&world, vec2(16.0, 0.0), 8, 8));
+    world.set_solid_collidable(door, false);
+    assert!(!collides(&world, vec2(16.0, 0.0), 8, 8));
+
+    world.solid_move(door, -16.0, 0.0);
+    assert_eq!(world.actor_pos(actor), vec2(0.0, 0.0));
+    assert!(!world.solid_collidable(door));
+}
+
+#[test]
+fn remove_actor_keeps_other_handles_valid() {
+    let mut world = World::new();
+    let a = world.add_actor(vec2(0.0, 0.0), 8, 8);
+    let b = world.add_actor(vec2(50.0, 0.0), 8, 8);
+    let c = world.add_actor(vec2(100.0, 0.0), 8, 8);
+
+    assert!(world.remove_actor(a));
+
+    assert_eq!(world.actor_pos(b), vec2(50.0, 0.0));
+    assert_eq!(world.actor_pos(c), vec2(100.0, 0.0));
+    assert!(!world.contains_actor(a));
+    assert!(!world.remove_actor(a));
+}
+
+#[test]
+fn stale_handles_do_not_alias_reused_slots() {
+    let mut world = World::new();
+    let old = world.add_actor(vec2(0.0, 0.0), 8, 8);
+    world.remove_actor(old);
+    let new = world.add_actor(vec2(30.0, 0.0), 8, 8);
+
+    assert_ne!(old, new);
+    assert!(!world.contains_actor(old));
+    assert!(!world.remove_actor(old));
+    assert!(world.contains_actor(new));
+
+    let old_solid = world.add_solid(vec2(0.0, 0.0), 8, 8);
+    world.remove_solid_handle(old_solid);
+    let new_solid = world.add_solid(vec2(0.0, 0.0), 8, 8);
+    assert!(!world.remove_solid_handle(old_solid));
+    assert!(world.contains_solid(new_solid));
+}
+
+#[test]
+#[should_panic(expected = "stale or invalid Actor handle")]
+fn stale_actor_handles_panic_on_access() {
+    let mut world = World::new();
+    let actor = world.add_actor(vec2(0.0, 0.0), 8, 8);
+    world.remove_actor(actor);
+    world.add_actor(vec2(30.0, 0.0), 8, 8);
+
+    world.actor_pos(actor);
+}
+
+#[test]
+fn remove_solid_by_position() {
+    let mut world = World::new();
+    let solid = world.add_solid(vec2(16.0, 16.0), 16, 16);
+
+    assert_eq!(world.remove_solid(vec2(0.0, 0.0)), None);
+    assert_eq!(world.remove_solid(vec2(16.0, 16.0)), Some(solid));
+    assert!(!collides(&world, vec2(16.0, 16.0), 8, 8));
+}
+
+#[test]
+fn solids_in_rect_follows_moving_solids() {
+    let mut world = World::new();
+    let solid = world.add_solid(vec2(0.0, 0.0), 16, 16);
+
+    assert_eq!(
+        world.solids_in_rect(Rect::new(0.0, 0.0, 8.0, 8.0)),
+        vec![solid]
+    );
+    world.solid_move(solid, 200.0, 0.0);
+    assert!(world
+        .solids_in_rect(Rect::new(0.0, 0.0, 8.0, 8.0))
+        .is_empty());
+    assert_eq!(
+        world.solids_in_rect(Rect::new(200.0, 0.0, 8.0, 8.0)),
+        vec![solid]
+    );
+}
+
+#[test]
+fn triggers_report_enter_stay_and_exit() {
+    let mut world = World::new();
+    let trigger = world.add_trigger(Rect::new(32.0, 0.0, 16.0, 16.0), 7, 42);
+    let actor = world.add_actor(vec2(0.0, 0.0), 8, 8);
+
+    assert!(world.update_triggers().is_empty());
+
+    world.set_actor_position(actor, vec2(36.0, 4.0));
+    let events = world.update_triggers();
+    assert_eq!(
+        events,
+        vec![TriggerEvent {
+            trigger,
+            actor,
+            kind: TriggerEventKind::Enter,
+            tag: 7,
+            user_data: 42,
+        }]
+    );
+    assert_eq!(world.update_triggers()[0].kind, TriggerEventKind::Stay);
+
+    world.set_actor_position(actor, vec2(100.0, 4.0));
+    assert_eq!(world.update_triggers()[0].kind, TriggerEventKind::Exit);
+    assert!(world.update_triggers().is_empty());
+}
+
+#[test]
+fn raycast_hits_tiles_solids_and_actors() {
+    let mut world = world(&[".......#"], 16.0, 16.0);
+
+    let hit = world
+        .raycast(vec2(4.0, 8.0), vec2(1.0, 0.0), 500.0)
+        .unwrap();
+    assert_eq!(hit.point, vec2(112.0, 8.0));
+    assert_eq!(hit.normal, vec2(-1.0, 0.0));
+    assert_eq!(hit.tile, Tile::Solid);
+
+    let solid = world.add_solid(vec2(64.0, 0.0), 16, 16);
+    let hit = world
+        .raycast(vec2(4.0, 8.0), vec2(1.0, 0.0), 500.0)
+        .unwrap();
+    assert_eq!(hit.solid, Some(solid));
+    assert_eq!(hit.distance, 60.0);
+
+    let actor = world.add_actor(vec2(32.0, 4.0), 8, 8);
+    let hit = world
+        .raycast(vec2(4.0, 8.0), vec2(1.0, 0.0), 500.0)
+        .unwrap();
+    assert_eq!(hit.actor, Some(actor));
+
+    assert!(world
+        .raycast(vec2(4.0, 8.0), vec2(-1.0, 0.0), 500.0)
+        .is_none());
+    assert!(world
+        .raycast(vec2(4.0, 8.0), vec2(1.0, 0.0), 20.0)
+        .is_none());
+}
+
+#[test]
+fn sweep_box_stops_at_the_first_obstacle() {
+    let mut world = world(&["......#."], 16.0, 16.0);
+    let actor = world.add_actor(vec2(0.0, 0.0), 16, 16);
+
+    let hit = world.sweep_box(actor, vec2(200.0, 0.0)).unwrap();
+    assert_eq!(hit.point, vec2(80.0, 0.0));
+    assert_eq!(hit.normal, vec2(-1.0, 0.0));
+    assert!(world.sweep_box(actor, vec2(40.0, 0.0)).is_none());
+    // sweeping does not move the actor
+    assert_eq!(world.actor_pos(actor), vec2(0.0, 0.0));
+}
+
+#[test]
+fn offset_layers_shift_every_query() {
+    let (tiles, width) = layer(&["..", ".#"]);
+    let mut world = World::new();
+    world.add_static_tiled_layer_at(
+        vec2(100.0, 50.0),
+        tiles,
+        16.0,
+        32.0,
+        width,
+        CollisionLayers::SOLID,
+    );
+
+    // the solid tile covers x 116..132, y 82..114
+    assert!(world.tag_at(vec2(116.0, 82.0), CollisionLayers::SOLID));
+    assert!(!world.tag_at(vec2(16.0, 32.0), CollisionLayers::SOLID));
+    assert!(collides(&world, vec2(110.0, 100.0), 8, 8));
+    assert!(!collides(&world, vec2(108.0, 100.0), 8, 8));
+
+    let actor = world.add_actor(vec2(116.0, 0.0), 16, 16);
+    let hit = world.sweep_box(actor, vec2(0.0, 200.0)).unwrap();
+    assert_eq!(hit.point, vec2(116.0, 66.0));
+    assert!(world.move_actor(actor, vec2(0.0, 100.0)).blocked_y);
+    assert_eq!(world.actor_pos(actor), vec2(116.0, 66.0));
+}
+
+// Row of three tiles: a half-height tile, a slope rising to the right and a full tile
+fn shaped_world() -> World {
+    let mut world = World::new();
+    let (tiles, width) = layer(&["###"]);
+    let layer = world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::SOLID);
+    world.set_tile_shapes(
+        layer,
+        0,
+        0,
+        &[TileShape::Rect(Rect::new(0.0, 8.0, 16.0, 8.0))],
+    );
+    world.set_tile_shapes(
+        layer,
+        1,
+        0,
+        &[TileShape::Polygon(vec![
+            vec2(0.0, 16.0),
+            vec2(16.0, 0.0),
+            vec2(16.0, 16.0),
+        ])],
+    );
+    world
+}
+
+#[test]
+fn tile_shapes_only_block_their_area() {
+    let world = shaped_world();
+
+    // half-height tile
+    assert!(!world.solid_at(vec2(8.0, 4.0)));
+    assert!(world.solid_at(vec2(8.0, 12.0)));
+    assert!(!collides(&world, vec2(0.0, 0.0), 16, 8));
+    assert!(collides(&world, vec2(0.0, 1.0), 16, 8));
+    // slope, solid below the diagonal
+    assert!(!world.solid_at(vec2(20.0, 4.0)));
+    assert!(world.solid_at(vec2(28.0, 12.0)));
+    assert!(!collides(&world, vec2(16.0, 0.0), 4, 4));
+    assert!(collides(&world, vec2(16.0, 9.0), 4, 4));
+    // the full tile still blocks everywhere
+    assert!(world.solid_at(vec2(36.0, 1.0)));
+}
+
+#[test]
+fn actors_walk_over_the_empty_part_of_shaped_tiles() {
+    let mut world = shaped_world();
+    let actor = world.add_actor(vec2(0.0, 0.0), 8, 8);
+
+    assert!(world.move_h(actor, 8.0));
+    assert_eq!(world.actor_pos(actor), vec2(8.0, 0.0));
+    // lands on top of the half-height tile
+    assert!(!world.move_v(actor, 8.0));
+    assert_eq!(world.actor_pos(actor), vec2(8.0, 0.0));
+    // stopped by the slope where it rises to the actor's bottom edge
+    assert!(!world.move_h(actor, 16.0));
+    assert_eq!(world.actor_pos(actor), vec2(16.0, 0.0));
+}
+
+#[test]
+fn raycasts_and_sweeps_hit_tile_shapes() {
+    let mut world = shaped_world();
+
+    let hit = world
+        .raycast(vec2(4.0, -10.0), vec2(0.0, 1.0), 100.0)
+        .unwrap();
+    assert_eq!(hit.point, vec2(4.0, 8.0));
+    assert_eq!(hit.normal, vec2(0.0, -1.0));
+    // the slope's diagonal, x + y = 32 in world space
+    let hit = world
+        .raycast(vec2(20.0, -10.0), vec2(0.0, 1.0), 100.0)
+        .unwrap();
+    assert_eq!(hit.point, vec2(20.0, 12.0));
+    assert!((hit.normal - vec2(-1.0, -1.0).normalize()).length() < 1e-6);
+
+    let actor = world.add_actor(vec2(4.0, -20.0), 4, 4);
+    let hit = world.sweep_box(actor, vec2(0.0, 40.0)).unwrap();
+    assert_eq!(hit.point, vec2(4.0, 4.0));
+    // the box's bottom right corner touches the diagonal first
+    world.set_actor_position(actor, vec2(18.0, -20.0));
+    let hit = world.sweep_box(actor, vec2(0.0, 40.0)).unwrap();
+    assert!((hit.point - vec2(18.0, 6.0)).length() < 1e-4);
+}
+
+#[test]
+fn cleared_layers_stop_blocking() {
+    let mut world = shaped_world();
+    world.clear_static_tiled_layers();
+    assert!(!world.solid_at(vec2(36.0, 1.0)));
+
+    let (tiles, width) = layer(&["#"]);
+    world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::SOLID);
+    assert!(world.solid_at(vec2(8.0, 8.0)));
+}
+
+#[test]
+fn removed_layers_stop_blocking_and_their_handles_go_stale() {
+    let mut world = World::new();
+    let (tiles, width) = layer(&["#."]);
+    let left = world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::SOLID);
+    let (tiles, width) = layer(&[".#"]);
+    let right = world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::SOLID);
+
+    assert!(world.remove_static_tiled_layer(left));
+    assert!(!world.solid_at(vec2(8.0, 8.0)));
+    assert!(world.solid_at(vec2(24.0, 8.0)));
+    assert!(!world.remove_static_tiled_layer(left));
+
+    // the freed slot is reused without reviving the old handle
+    let (tiles, width) = layer(&["#"]);
+    let reused = world.add_static_tiled_layer(tiles, 16.0, 16.0, width, CollisionLayers::SOLID);
+    assert_ne!(reused, left);
+    assert!(!world.remove_static_tiled_layer(left));
+    assert!(world.remove_static_tiled_layer(right));
+    assert!(world.solid_at(vec2(8.0, 8.0)));
+}
+
+// Every leg of `path` from `start` is clear for `actor`
+fn walkable(world: &World, actor: Actor, start: Vec2, path: &[Vec2]) -> bool {
+    let mut from = start;
+    path.iter().all(|&to| {
+        let clear = world
+            .sweep_box_from(actor, from, to - from)
+            .is_none_or(|hit| hit.distance >= from.distance(to) - 1e-3);
+        from = to;
+        clear
+    })
+}
+
+#[test]
+fn paths_go_around_walls_in_straight_legs() {
+    let world_rows = ["......", "..#...", "..#...", "..#...", "......"];
+    let mut world = world(&world_rows, 16.0, 16.0);
+    let actor = world.add_actor(vec2(0.0, 32.0), 16, 16);
+    let bounds = Rect::new(0.0, 0.0, 96.0, 80.0);
+
+    let path = world.find_path(actor, vec2(64.0, 32.0), bounds).unwrap();
+    assert_eq!(path.last(), Some(&vec2(64.0, 32.0)));
+    assert!(walkable(&world, actor, vec2(0.0, 32.0), &path));
+    // around one end of the wall, not cell by cell
+    assert!(path.len() <= 3, "{path:?}");
+
+    // in the open it's a straight line
+    let path = world.find_path(actor, vec2(0.0, 64.0), bounds).unwrap();
+    assert_eq!(path, vec![vec2(0.0, 64.0)]);
+}
+
+#[test]
+fn unreachable_targets_have_no_path() {
+    let world_rows = ["......", ".###..", ".#.#..", ".###..", "......"];
+    let mut world = world(&world_rows, 16.0, 16.0);
+    let actor = world.add_actor(vec2(0.0, 0.0), 16, 16);
+    let bounds = Rect::new(0.0, 0.0, 96.0, 80.0);
+
+    // inside a wall, walled in and out of bounds
+    assert!(world.find_path(actor, vec2(16.0, 16.0), bounds).is_none());
+    assert!(world.find_path(actor, vec2(32.0, 32.0), bounds).is_none());
+    assert!(world.find_path(actor, vec2(90.0, 0.0), bounds).is_none());
+    assert!(world.find_path(actor, vec2(80.0, 64.0), bounds).is_some());
+}
+
+#[test]
+fn closed_doors_block_paths() {
+    let world_rows = ["..#..", "..#..", ".....", "..#..", "..#.."];
+    let mut world = world(&world_rows, 16.0, 16.0);
+    let door = world.add_solid(vec2(32.0, 32.0), 16, 16);
+    let actor = world.add_actor(vec2(0.0, 0.0), 16, 16);
+    let bounds = Rect::new(0.0, 0.0, 80.0, 80.0);
+    let target = vec2(64.0, 0.0);
+
+    assert!(world.find_path(actor, target, bounds).is_none());
+    world.set_solid_collidable(door, false);
+    let path = world.find_path(actor, target, bounds).unwrap();
+    assert_eq!(path.last(), Some(&target));
+    assert!(walkable(&world, actor, vec2(0.0, 0.0), &path));
+}
+
+#[test]
+fn paths_find_gaps_off_the_grid() {
+    // the gap is x 20..36, exactly as wide as the actor and half a path
+    // cell off the grid
+    let (tiles, width) = layer(&[".....", "#.###", "#.###", "....."]);
+    let mut world = World::new();
+    world.add_static_tiled_layer_at(
+        vec2(4.0, 0.0),
+        tiles,
+        16.0,
+        16.0,
+        width,
+        CollisionLayers::SOLID,
+    );
+    let actor = world.add_actor(vec2(56.0, 0.0), 16, 16);
+    let bounds = Rect::new(4.0, 0.0, 80.0, 64.0);
+
+    let path = world.find_path(actor, vec2(20.0, 24.0), bounds).unwrap();
+    assert_eq!(path.last(), Some(&vec2(20.0, 24.0)));
+    assert!(walkable(&world, actor, vec2(56.0, 0.0), &path));
+
+    let path = world.find_path(actor, vec2(56.0, 48.0), bounds).unwrap();
+    assert_eq!(path.last(), Some(&vec2(56.0, 48.0)));
+    assert!(walkable(&world, actor, vec2(56.0, 0.0), &path));
+}
+
+#[test]
+fn walled_in_targets_off_the_grid_have_no_path() {
+    // a room exactly the actor's size at 20, 20, no grid point nearby fits
+    let (tiles, width) = layer(&["###", "#.#", "###"]);
+    let mut world = World::new();
+    world.add_static_tiled_layer_at(
+        vec2(4.0, 4.0),
+        tiles,
+        16.0,
+        16.0,
+        width,
+        CollisionLayers::SOLID,
+    );
+    let actor = world.add_actor(vec2(60.0, 0.0), 16, 16);
+    let bounds = Rect::new(0.0, 0.0, 80.0, 80.0);
+
+    assert!(world.find_path(actor, vec2(20.0, 20.0), bounds).is_none());
+}
+
+// Walls around a 10x10 tile room with random pillars inside
+fn arena_tiles() -> impl Strategy<Value = Vec<bool>> {
+    prop::collection::vec(prop::bool::weighted(0.15), 100).prop_map(|mut tiles| {
+        for i in 0..10 {
+            tiles[i] = true;
+            tiles[90 + i] = true;
+            tiles[i * 10] = true;
+            tiles[i * 10 + 9] = true;
+        }
+        tiles
+    })
+}
+
+proptest! {
+    #[test]
+    fn actor_never_ends_up_inside_a_solid(
+        tiles in arena_tiles(),
+        solids in prop::collection::vec((16..140i32, 16..140i32, 4..24i32, 4..24i32), 0..4),
+        start in (16..140i32, 16..140i32),
+        size in (4..20i32, 4..20i32),
+        moves in prop::collection::vec((-24.0..24.0f32, -24.0..24.0f32), 1..40),
+    ) {
+        let mut world = World::new();
+        let static_colliders = tiles
+            .iter()
+            .map(|&solid| if solid { Tile::Solid } else { Tile::Empty })
+            .collect();
+        world.add_static_tiled_layer(static_colliders, 16.0, 16.0, 10, CollisionLayers::SOLID);
+        let solid_rects: Vec<Rect> = solids
+            .iter()
+            .map(|&(x, y, w, h)| {
+                world.add_solid(vec2(x as f32, y as f32), w, h);
+                Rect::new(x as f32, y as f32, w as f32, h as f32)
+            })
+            .collect();
+
+        let start = vec2(start.0 as f32, start.1 as f32);
+        prop_assume!(!collides(&world, start, size.0, size.1));
+        let actor = world.add_actor(start, size.0, size.1);
+
+        for (dx, dy) in moves {
+            world.move_actor(actor, vec2(dx, dy));
+
+            let rect = Rect::new(
+                world.actor_pos(actor).x,
+                world.actor_pos(actor).y,
+                size.0 as f32,
+                size.1 as f32,
+            );
+            for (i, &solid) in tiles.iter().enumerate() {
+                let tile = Rect::new((i % 10) as f32 * 16.0, (i / 10) as f32 * 16.0, 16.0, 16.0);
+                prop_assert!(!(solid && overlaps(&rect, &tile)), "inside tile {}", i);
+            }
+            for solid in &solid_rects {
+                prop_assert!(!overlaps(&rect, solid), "inside solid {:?}", solid);
+            }
+        }
+    }
+
+    #[test]
+    fn broadphase_matches_brute_force(
+        solids in prop::collection::vec((-200..200i32, -200..200i32, 1..80i32, 1..80i32), 0..20),
+        moves in prop::collection::vec((0..20usize, -100.0..100.0f32, -100.0..100.0f32), 0..10),
+        probe in (-250..250i32, -250..250i32, 1..60i32, 1..60i32),
+    ) {
+        let mut world = World::new();
+        let handles: Vec<Solid> = solids
+            .iter()
+            .map(|&(x, y, w, h)| world.add_solid(vec2(x as f32, y as f32), w, h))
+            .collect();
+        for (index, dx, dy) in moves {
+            if let Some(&solid) = handles.get(index) {
+                world.solid_move(solid, dx, dy);
+            }
+        }
+
+        let probe_rect = Rect::new(probe.0 as f32, probe.1 as f32, probe.2 as f32, probe.3 as f32);
+        let expected = handles.iter().any(|&solid| {
+            let pos = world.solid_pos(solid);
+            let (_, _, w, h) = solids[handles.iter().position(|&s| s == solid).unwrap()];
+            overlaps(&Rect::new(pos.x, pos.y, w as f32, h as f32), &probe_rect)
+        });
+
+        prop_assert_eq!(
+            collides(&world, vec2(probe_rect.x, probe_rect.y), probe.2, probe.3),
+            expected
+        );
+    }
+}
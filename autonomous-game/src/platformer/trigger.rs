@@ -0,0 +1,115 @@
+use macroquad::math::Rect;
+
+use std::collections::HashSet;
+
+use super::{impl_handle, overlaps, Actor, Handle, World};
+
+/// Generational handle to a trigger volume, see [`Actor`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Trigger {
+    index: usize,
+    generation: u32,
+}
+
+impl_handle!(Trigger);
+
+/// Non-blocking sensor. Never collides, only reports the actors inside it.
+pub(super) struct TriggerVolume {
+    rect: Rect,
+    tag: u32,
+    user_data: u64,
+    // actors inside the volume as of the last `update_triggers`
+    inside: HashSet<Actor>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerEventKind {
+    Enter,
+    Stay,
+    Exit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub trigger: Trigger,
+    pub actor: Actor,
+    pub kind: TriggerEventKind,
+    pub tag: u32,
+    pub user_data: u64,
+}
+
+impl World {
+    /// Registers a sensor volume. `tag` and `user_data` are opaque to the world
+    /// and handed back in every event of this trigger.
+    pub fn add_trigger(&mut self, rect: Rect, tag: u32, user_data: u64) -> Trigger {
+        self.triggers.insert(TriggerVolume {
+            rect,
+            tag,
+            user_data,
+            inside: HashSet::new(),
+        })
+    }
+
+    /// Removes the trigger without emitting exit events.
+    /// Returns `false` if the handle was already stale.
+    pub fn remove_trigger(&mut self, trigger: Trigger) -> bool {
+        self.triggers.remove(trigger).is_some()
+    }
+
+    pub fn set_trigger_rect(&mut self, trigger: Trigger, rect: Rect) {
+        self.triggers[trigger].rect = rect;
+    }
+
+    pub fn trigger_rect(&self, trigger: Trigger) -> Rect {
+        self.triggers[trigger].rect
+    }
+
+    pub fn trigger_tag(&self, trigger: Trigger) -> u32 {
+        self.triggers[trigger].tag
+    }
+
+    pub fn trigger_user_data(&self, trigger: Trigger) -> u64 {
+        self.triggers[trigger].user_data
+    }
+
+    /// Compares every trigger against the current actor positions, call once
+    /// per tick after moving actors. Removed actors get an exit event.
+    pub fn update_triggers(&mut self) -> Vec<TriggerEvent> {
+        let mut events = vec![];
+
+        for (trigger, volume) in self.triggers.iter_mut() {
+            let inside: HashSet<Actor> = self
+                .actor_grid
+                .query(volume.rect)
+                .into_iter()
+                .filter(|&actor| overlaps(&self.actors[actor].rect(), &volume.rect))
+                .collect();
+
+            let mut event = |actor: Actor, kind: TriggerEventKind| {
+                events.push(TriggerEvent {
+                    trigger,
+                    actor,
+                    kind,
+                    tag: volume.tag,
+                    user_data: volume.user_data,
+                })
+            };
+            for &actor in &volume.inside {
+                if !inside.contains(&actor) {
+                    event(actor, TriggerEventKind::Exit);
+                }
+            }
+            for &actor in &inside {
+                if volume.inside.contains(&actor) {
+                    event(actor, TriggerEventKind::Stay);
+                } else {
+                    event(actor, TriggerEventKind::Enter);
+                }
+            }
+
+            volume.inside = inside;
+        }
+
+        events
+    }
+}
@@ -0,0 +1,439 @@
+//! The map the player is on, and the switch between maps through portals.
+//!
+//! Maps are loaded a chunk at a time around the camera, from Tiled's
+//! infinite-map chunks or fixed-size maps cut the same way. A loaded chunk
+//! has its colliders in the world and its tiles ready to draw, far ones
+//! drop both. Only the gids of chunks with tiles stay in memory otherwise.
+
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::camera::{Effect, Effects};
+use crate::door::Door;
+use crate::map::{Entity, LayerData, MapData, TileAnimations, TileSprite, CHUNK_SIZE};
+use crate::platformer::{TiledLayer, Trigger, World};
+use crate::tiles::TileTextures;
+
+/// Maps portals can lead to, by the name used in their `map` property.
+pub const MAPS: &[(&str, &str)] = &[
+    ("island", "assets/map.json"),
+    ("cellar", "assets/cellar.json"),
+];
+
+// Trigger tags, the user data is the index of the entity in its scene
+pub const DOOR_TRIGGER: u32 = 1;
+pub const INTERACT_TRIGGER: u32 = 2;
+pub const PORTAL_TRIGGER: u32 = 3;
+const DOOR_FRAMES: usize = 6;
+
+// Chunks load this many chunks around the view and unload once they're
+// further than `UNLOAD_MARGIN`, so walking along a border doesn't thrash
+const LOAD_MARGIN: i32 = 1;
+const UNLOAD_MARGIN: i32 = 2;
+
+pub struct Scene {
+    pub name: String,
+    pub data: MapData,
+    pub animations: TileAnimations,
+    textures: TileTextures,
+    pub doors: Vec<Door>,
+    /// `action` of each interactable
+    pub interactions: Vec<String>,
+    /// `map` and `spawn` of each portal
+    pub portals: Vec<(Option<String>, String)>,
+    /// Where the camera pans the first time the player walks in
+    pub pan: Option<Vec2>,
+    triggers: Vec<Trigger>,
+    // loaded chunks, by chunk column and row
+    chunks: HashMap<(i32, i32), Chunk>,
+}
+
+// A chunk near the camera
+struct Chunk {
+    colliders: Vec<TiledLayer>,
+    // per tile layer, bottom to top
+    tiles: Vec<Vec<PlacedTile>>,
+}
+
+/// A tile of a loaded chunk.
+pub struct PlacedTile {
+    pub x: i32,
+    pub y: i32,
+    pub sprite: TileSprite,
+    /// World y it's drawn in order of on `ysort` layers, see `MapData::depth`
+    pub depth: f32,
+}
+
+impl Scene {
+    /// Loads a registered map. Nothing is added to the world until `enter`.
+    pub async fn load(name: &str, textures: &[(&str, Texture2D)]) -> Result<Scene, String> {
+        let (_, path) = MAPS
+            .iter()
+            .find(|(map, _)| *map == name)
+            .ok_or_else(|| format!("no map called {name:?}"))?;
+        let json = load_string(path)
+            .await
+            .map_err(|err| format!("{path}: {err}"))?;
+
+        let data = MapData::from_json(&json).map_err(|err| format!("{path}: {err}"))?;
+        let textures =
+            TileTextures::new(&data, textures).map_err(|err| format!("{path}: {err}"))?;
+
+        Ok(Scene {
+            name: name.to_owned(),
+            animations: data.tile_animations(),
+            data,
+            textures,
+            doors: vec![],
+            interactions: vec![],
+            portals: vec![],
+            pan: None,
+            triggers: vec![],
+            chunks: HashMap::new(),
+        })
+    }
+
+    /// Adds the map's entities to the world. Tiles are loaded by `stream` as
+    /// chunks come into view.
+    pub async fn enter(&mut self, world: &mut World) {
+        // Entities come from the map's object layers
+        for entity in self.data.entities() {
+            match entity {
+                Entity::Spawn { .. } => {}
+                Entity::Door { rect } => {
+                    // the door can be used from either side
+                    let index = self.doors.len() as u64;
+                    let trigger = world.add_trigger(interaction_zone(rect), DOOR_TRIGGER, index);
+                    self.triggers.push(trigger);
+                    self.doors
+                        .push(Door::new(rect.point(), DOOR_FRAMES, world).await);
+                }
+                Entity::Interact { rect, action } => {
+                    let index = self.interactions.len() as u64;
+                    let trigger = world.add_trigger(rect, INTERACT_TRIGGER, index);
+                    self.triggers.push(trigger);
+                    self.interactions.push(action);
+                }
+                Entity::Portal { rect, map, spawn } => {
+                    let index = self.portals.len() as u64;
+                    let trigger = world.add_trigger(rect, PORTAL_TRIGGER, index);
+                    self.triggers.push(trigger);
+                    self.portals.push((map, spawn));
+                }
+                Entity::Pan { position } => self.pan = Some(position),
+            }
+        }
+    }
+
+    /// Removes everything `enter` added to the world. Actors stay.
+    pub fn leave(&mut self, world: &mut World) {
+        for (_, chunk) in self.chunks.drain() {
+            for layer in chunk.colliders {
+                world.remove_static_tiled_layer(layer);
+            }
+        }
+        for door in self.doors.drain(..) {
+            door.remove(world);
+        }
+        for trigger in self.triggers.drain(..) {
+            world.remove_trigger(trigger);
+        }
+        self.interactions.clear();
+        self.portals.clear();
+        self.pan = None;
+    }
+
+    /// Loads the chunks around `view`, a world rect, and unloads the far
+    /// ones. Returns the world rect of the chunks around `view`, which are
+    /// all loaded.
+    pub fn stream(&mut self, world: &mut World, view: Rect) -> Rect {
+        let near = self.chunk_range(view, LOAD_MARGIN);
+        let far = self.chunk_range(view, UNLOAD_MARGIN);
+
+        self.chunks.retain(|&(x, y), chunk| {
+            let keep = far.0.contains(&x) && far.1.contains(&y);
+            if !keep {
+                for &layer in &chunk.colliders {
+                    world.remove_static_tiled_layer(layer);
+                }
+            }
+            keep
+        });
+
+        for y in near.1.clone() {
+            for x in near.0.clone() {
+                if !self.chunks.contains_key(&(x, y)) {
+                    let chunk = self.load_chunk(world, (x, y));
+                    self.chunks.insert((x, y), chunk);
+                }
+            }
+        }
+
+        let chunk = self.chunk_size();
+        let (columns, rows) = near;
+        Rect::new(
+            columns.start as f32 * chunk.x,
+            rows.start as f32 * chunk.y,
+            columns.len() as f32 * chunk.x,
+            rows.len() as f32 * chunk.y,
+        )
+    }
+
+    // Adds a chunk's colliders and finds what to draw in it
+    fn load_chunk(&self, world: &mut World, (x, y): (i32, i32)) -> Chunk {
+        // Colliders come from the `collision` property of the map's tiles
+        let colliders = self.data.add_collision_region(
+            world,
+            x * CHUNK_SIZE,
+            y * CHUNK_SIZE,
+            CHUNK_SIZE,
+            CHUNK_SIZE,
+        );
+        let tiles = self
+            .tile_layers()
+            .map(|layer| {
+                let ysort = layer.ysort();
+                layer
+                    .chunk_tiles((x, y))
+                    .filter_map(|(x, y, raw_gid)| {
+                        Some(PlacedTile {
+                            x,
+                            y,
+                            sprite: self.data.tile_sprite(raw_gid)?,
+                            depth: if ysort {
+                                self.data.depth(layer, x, y)
+                            } else {
+                                0.0
+                            },
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        Chunk { colliders, tiles }
+    }
+
+    // Size of a chunk in world pixels
+    fn chunk_size(&self) -> Vec2 {
+        self.tile_size() * CHUNK_SIZE as f32
+    }
+
+    fn tile_size(&self) -> Vec2 {
+        vec2(self.data.tilewidth as f32, self.data.tileheight as f32)
+    }
+
+    // Columns and rows of the map's chunks within `margin` chunks of `view`
+    fn chunk_range(&self, view: Rect, margin: i32) -> (Range<i32>, Range<i32>) {
+        let (columns, rows) = self.data.chunk_extent();
+        let near = cells_touching(view, self.chunk_size());
+        let range = |near: Range<i32>, map: Range<i32>| {
+            let start = (near.start - margin).clamp(map.start, map.end);
+            start..(near.end + margin).clamp(start, map.end)
+        };
+        (range(near.0, columns), range(near.1, rows))
+    }
+
+    /// Columns and rows of the cells with tiles that can be drawn in `rect`,
+    /// in world pixels. Tiles taller than the grid stick out of the top of
+    /// their cell, and layers are shifted by their offset.
+    pub fn cells_touching(&self, rect: Rect) -> (Range<i32>, Range<i32>) {
+        let overhang = self
+            .data
+            .tilesets
+            .iter()
+            .map(|tileset| tileset.tileheight.saturating_sub(self.data.tileheight))
+            .max()
+            .unwrap_or(0) as f32;
+        let offset = self
+            .tile_layers()
+            .map(|layer| layer.offset().abs().max_element())
+            .fold(0.0, f32::max);
+        let reach = overhang + offset;
+        let rect = Rect::new(
+            rect.x - reach,
+            rect.y - reach,
+            rect.w + 2.0 * reach,
+            rect.h + 2.0 * reach,
+        );
+        cells_touching(rect, self.tile_size())
+    }
+
+    /// Tile layers, bottom to top.
+    pub fn tile_layers(&self) -> impl Iterator<Item = &LayerData> {
+        self.data
+            .layers
+            .iter()
+            .filter(|layer| layer.ty == "tilelayer")
+    }
+
+    /// Tiles of the loaded chunks on the `index`th tile layer, in the
+    /// `columns` and `rows` of cells. Chunk by chunk, top to bottom and left
+    /// to right, and row by row in each chunk.
+    pub fn tiles(
+        &self,
+        index: usize,
+        (columns, rows): (Range<i32>, Range<i32>),
+    ) -> impl Iterator<Item = &PlacedTile> {
+        let mut chunks: Vec<_> = self.chunks.iter().collect();
+        // the draw order of overlapping sprites shouldn't depend on the hash map
+        chunks.sort_by_key(|&(&(x, y), _)| (y, x));
+        chunks
+            .into_iter()
+            .flat_map(move |(_, chunk)| &chunk.tiles[index])
+            .filter(move |tile| columns.contains(&tile.x) && rows.contains(&tile.y))
+    }
+
+    /// Draws `tile` of `layer`, shifted by the layer offset like its
+    /// colliders, at its animation frame at `time`.
+    pub fn draw_tile(&self, layer: &LayerData, tile: &PlacedTile, time: f64) {
+        let size = self.tile_size();
+        let cell = Rect::new(
+            tile.x as f32 * size.x + layer.offsetx,
+            tile.y as f32 * size.y + layer.offsety,
+            size.x,
+            size.y,
+        );
+        self.textures
+            .draw(&self.data, &self.animations, tile.sprite, cell, time);
+    }
+}
+
+// Columns and rows of the cells of `size` that `rect` touches
+fn cells_touching(rect: Rect, size: Vec2) -> (Range<i32>, Range<i32>) {
+    let min = (rect.point() / size).floor();
+    let max = (vec2(rect.right(), rect.bottom()) / size).floor();
+    (
+        min.x as i32..max.x as i32 + 1,
+        min.y as i32..max.y as i32 + 1,
+    )
+}
+
+// The player collider overlaps this rect when the player is within 8px of `rect`
+fn interaction_zone(rect: Rect) -> Rect {
+    Rect::new(rect.x - 8.0, rect.y - 8.0, rect.w + 16.0, rect.h + 16.0)
+}
+
+/// Seconds to fade out, and again to fade back in
+const FADE_TIME: f32 = 0.3;
+// Letterbox bars while the map changes, as a fraction of the screen height
+const LETTERBOX: f32 = 0.08;
+
+/// Fade to black, move the player while the screen is black, fade back in.
+pub struct Transition {
+    /// Map to switch to, `None` to stay on the current one
+    pub map: Option<String>,
+    pub spawn: String,
+    swapped: bool,
+}
+
+impl Transition {
+    /// Starts fading the camera out.
+    pub fn start(map: Option<String>, spawn: String, effects: &mut Effects) -> Self {
+        effects.push(Effect::Fade {
+            alpha: 1.0,
+            duration: FADE_TIME,
+        });
+        effects.push(Effect::Letterbox {
+            amount: LETTERBOX,
+            duration: FADE_TIME,
+        });
+        Self {
+            map,
+            spawn,
+            swapped: false,
+        }
+    }
+
+    /// Returns `true` on the one update where the screen is fully black and
+    /// the player should be moved, and starts fading back in.
+    pub fn update(&mut self, effects: &mut Effects) -> bool {
+        if self.swapped || effects.fade_alpha() < 1.0 {
+            return false;
+        }
+        self.swapped = true;
+        effects.push(Effect::Fade {
+            alpha: 0.0,
+            duration: FADE_TIME,
+        });
+        effects.push(Effect::Letterbox {
+            amount: 0.0,
+            duration: FADE_TIME,
+        });
+        true
+    }
+
+    pub fn is_finished(&self, effects: &Effects) -> bool {
+        self.swapped && effects.fade_alpha() <= 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platformer::{CollisionLayers, Tile};
+
+    #[test]
+    fn portals_lead_to_spawns_clear_of_walls_and_portals() {
+        let load = |name: &str| {
+            let (_, path) = MAPS.iter().find(|(map, _)| *map == name).unwrap();
+            let json = std::fs::read_to_string(path).unwrap();
+            MapData::from_json(&json).unwrap()
+        };
+        for &(name, _) in MAPS {
+            for entity in load(name).entities() {
+                let Entity::Portal { map, spawn, .. } = entity else {
+                    continue;
+                };
+                let target = load(map.as_deref().unwrap_or(name));
+                let position = target.spawn(&spawn).unwrap();
+
+                // the player's box where it arrives
+                let arrival = Rect::new(position.x - 8.0, position.y - 8.0, 16.0, 16.0);
+                let mut world = World::new();
+                target.add_collision_layers(&mut world);
+                assert_eq!(
+                    world.collide_solids(
+                        CollisionLayers::SOLID | CollisionLayers::WATER,
+                        arrival.point(),
+                        16,
+                        16
+                    ),
+                    Tile::Empty,
+                    "{name} -> {spawn}"
+                );
+                // arriving on a portal would send the player straight back
+                for entity in target.entities() {
+                    if let Entity::Portal { rect, .. } = entity {
+                        assert!(!rect.overlaps(&arrival), "{name} -> {spawn}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transitions_swap_once_on_black_and_finish_once_faded_in() {
+        let mut effects = Effects::default();
+        let mut transition = Transition::start(None, "cellar".to_owned(), &mut effects);
+
+        // not while fading out
+        effects.update(FADE_TIME / 2.0);
+        assert!(!transition.update(&mut effects));
+        assert!(!transition.is_finished(&effects));
+
+        effects.update(FADE_TIME);
+        assert!(transition.update(&mut effects));
+        // only once, and fading back in from black
+        assert!(!transition.update(&mut effects));
+        assert_eq!(effects.fade_alpha(), 1.0);
+        assert!(!transition.is_finished(&effects));
+
+        effects.update(FADE_TIME / 2.0);
+        assert!(!transition.update(&mut effects));
+        assert!(!transition.is_finished(&effects));
+        effects.update(FADE_TIME);
+        assert!(!transition.update(&mut effects));
+        assert!(transition.is_finished(&effects));
+    }
+}
@@ -0,0 +1,120 @@
+//! Draws map tiles from their tilesets' images the way Tiled does, flipped
+//! by the flags of their gid and standing on the bottom left corner of their
+//! cell.
+
+use macroquad::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+use crate::map::{MapData, TileAnimations, TileSprite};
+
+/// The image of each of a map's tilesets.
+pub struct TileTextures {
+    // by tileset index
+    textures: Vec<Texture2D>,
+}
+
+impl TileTextures {
+    /// Finds each tileset's image in `textures`, by file name.
+    pub fn new(map: &MapData, textures: &[(&str, Texture2D)]) -> Result<Self, String> {
+        let textures = map
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                textures
+                    .iter()
+                    .find(|(name, _)| *name == tileset.image)
+                    .map(|(_, texture)| texture.clone())
+                    .ok_or_else(|| format!("no texture {:?} for {:?}", tileset.image, tileset.name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { textures })
+    }
+
+    /// Draws `sprite` at its frame at `time` on the shared animation clock,
+    /// in `cell`, in world pixels.
+    pub fn draw(
+        &self,
+        map: &MapData,
+        animations: &TileAnimations,
+        sprite: TileSprite,
+        cell: Rect,
+        time: f64,
+    ) {
+        let tileset = &map.tilesets[sprite.tileset];
+        let source = tileset.source(animations.frame(&tileset.name, sprite.id, time));
+        let (flip_x, flip_y, rotation) = orientation(sprite.flips());
+        draw_texture_ex(
+            &self.textures[sprite.tileset],
+            cell.x,
+            cell.bottom() - source.h,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(source.size()),
+                // a tenth of a pixel in, so the neighbouring tiles don't bleed in
+                source: Some(Rect::new(
+                    source.x + 0.1,
+                    source.y + 0.1,
+                    source.w - 0.2,
+                    source.h - 0.2,
+                )),
+                rotation,
+                flip_x,
+                flip_y,
+                pivot: None,
+            },
+        );
+    }
+}
+
+// Texture flips and rotation for Tiled's horizontal, vertical and diagonal
+// flips. Macroquad flips the texture, then turns it clockwise around its
+// center. A diagonal flip is a quarter turn of the texture flipped
+// vertically, and turning swaps which way the other two flip.
+fn orientation((horizontal, vertical, diagonal): (bool, bool, bool)) -> (bool, bool, f32) {
+    if diagonal {
+        (vertical, !horizontal, FRAC_PI_2)
+    } else {
+        (horizontal, vertical, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation_matches_tiled_flips() {
+        let corners = [vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-2.0, 3.0)];
+        for flags in 0..8 {
+            let flips = (flags & 1 != 0, flags & 2 != 0, flags & 4 != 0);
+            let (horizontal, vertical, diagonal) = flips;
+            // Tiled: diagonal, then horizontal, then vertical
+            let tiled = |mut p: Vec2| {
+                if diagonal {
+                    p = vec2(p.y, p.x);
+                }
+                if horizontal {
+                    p.x = -p.x;
+                }
+                if vertical {
+                    p.y = -p.y;
+                }
+                p
+            };
+            // macroquad: flips, then the turn
+            let (flip_x, flip_y, rotation) = orientation(flips);
+            let macroquad = |mut p: Vec2| {
+                if flip_x {
+                    p.x = -p.x;
+                }
+                if flip_y {
+                    p.y = -p.y;
+                }
+                Vec2::from_angle(rotation).rotate(p)
+            };
+            for p in corners {
+                assert!(tiled(p).distance(macroquad(p)) < 1e-5, "{flips:?} {p}");
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! On-screen joystick and buttons for phones and tablets, which have no keys.
+
+use macroquad::prelude::*;
+use macroquad::ui::{root_ui, widgets};
+use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_8;
+
+use crate::input::{Action, Input};
+
+// Layout in screen points, from the bottom corners
+const MARGIN: f32 = 30.0;
+const STICK_RADIUS: f32 = 60.0;
+const KNOB_RADIUS: f32 = 25.0;
+// A finger landing this close to the stick grabs it, thumbs aren't precise
+const STICK_GRAB: f32 = STICK_RADIUS * 1.5;
+// Knob offsets under this fraction of the radius don't move the player
+const DEAD_ZONE: f32 = 0.3;
+const BUTTON_SIZE: Vec2 = vec2(120.0, 80.0);
+const BUTTON_GAP: f32 = 16.0;
+// Bottom to top
+const BUTTONS: &[(Action, &str)] = &[(Action::Interact, "Use"), (Action::ToggleBalance, "SUI")];
+// Same browns as the button text
+const STICK_COLOR: Color = Color::new(175. / 256., 139. / 256., 104. / 256., 0.4);
+const KNOB_COLOR: Color = Color::new(220. / 256., 185. / 256., 138. / 256., 0.8);
+
+#[derive(Default)]
+pub struct TouchControls {
+    /// Shown once the screen is touched, hidden again by a key press
+    visible: bool,
+    // finger holding the stick
+    stick: Option<u64>,
+    // fingers that landed on a button, until they lift
+    buttons: HashSet<u64>,
+    // where the knob is, from the center of the stick
+    knob: Vec2,
+}
+
+impl TouchControls {
+    /// Adds the stick and the buttons to this frame's `input`, the same
+    /// actions as their keys, and takes the fingers on them out of
+    /// `input.touches`.
+    pub fn update(&mut self, input: &mut Input) {
+        let touches = input.touches.clone();
+        if !touches.is_empty() {
+            self.visible = true;
+        }
+        if get_last_key_pressed().is_some() {
+            self.visible = false;
+        }
+        if !self.visible {
+            self.stick = None;
+            self.buttons.clear();
+            self.knob = Vec2::ZERO;
+            return;
+        }
+
+        let screen = vec2(screen_width(), screen_height());
+        let center = stick_center(screen);
+        // the stick's finger can lift between two frames without an `Ended`
+        if !touches.iter().any(|touch| Some(touch.id) == self.stick) {
+            self.stick = None;
+            self.knob = Vec2::ZERO;
+        }
+        self.buttons
+            .retain(|&id| touches.iter().any(|touch| touch.id == id));
+        let mut claimed = vec![];
+        for touch in &touches {
+            let position = touch.position;
+            let lifted = matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled);
+            if touch.phase == TouchPhase::Started {
+                if self.stick.is_none() && position.distance(center) <= STICK_GRAB {
+                    self.stick = Some(touch.id);
+                }
+                if let Some(index) = button_at(screen, position) {
+                    input.press(BUTTONS[index].0);
+                    self.buttons.insert(touch.id);
+                }
+                // A finger landing on a control isn't a tap to walk there, one
+                // landing anywhere else is if it's the only finger down
+                if covers(screen, position) {
+                    input.release(Action::MoveTo);
+                } else {
+                    input.pointer = position;
+                }
+            }
+            if self.buttons.contains(&touch.id) {
+                claimed.push(touch.id);
+                if lifted {
+                    self.buttons.remove(&touch.id);
+                }
+            }
+            if Some(touch.id) == self.stick {
+                claimed.push(touch.id);
+                if lifted {
+                    self.stick = None;
+                    self.knob = Vec2::ZERO;
+                } else {
+                    self.knob = (position - center).clamp_length_max(STICK_RADIUS);
+                }
+            }
+        }
+
+        // Fingers on the controls don't pinch the camera
+        input.touches.retain(|touch| !claimed.contains(&touch.id));
+
+        for action in stick_actions(self.knob) {
+            input.hold(action);
+        }
+    }
+
+    /// Draws in screen points, over the HUD.
+    pub fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        let screen = vec2(screen_width(), screen_height());
+        let center = stick_center(screen);
+        draw_circle(center.x, center.y, STICK_RADIUS, STICK_COLOR);
+        draw_circle_lines(center.x, center.y, STICK_RADIUS, 2.0, KNOB_COLOR);
+        let knob = center + self.knob;
+        draw_circle(knob.x, knob.y, KNOB_RADIUS, KNOB_COLOR);
+
+        // Drawn with the menu's skin, taps are read from the touches in
+        // `update` so holding the stick doesn't get in the way
+        for (index, &(_, label)) in BUTTONS.iter().enumerate() {
+            let rect = button_rect(screen, index);
+            widgets::Button::new(label)
+                .position(rect.point())
+                .size(rect.size())
+                .ui(&mut root_ui());
+        }
+    }
+}
+
+fn stick_center(screen: Vec2) -> Vec2 {
+    vec2(MARGIN + STICK_RADIUS, screen.y - MARGIN - STICK_RADIUS)
+}
+
+fn button_rect(screen: Vec2, index: usize) -> Rect {
+    let bottom = screen.y - MARGIN - index as f32 * (BUTTON_SIZE.y + BUTTON_GAP);
+    Rect::new(
+        screen.x - MARGIN - BUTTON_SIZE.x,
+        bottom - BUTTON_SIZE.y,
+        BUTTON_SIZE.x,
+        BUTTON_SIZE.y,
+    )
+}
+
+// Button under `position`, in screen points
+fn button_at(screen: Vec2, position: Vec2) -> Option<usize> {
+    (0..BUTTONS.len()).find(|&index| button_rect(screen, index).contains(position))
+}
+
+// Whether `position` is on the stick or a button
+fn covers(screen: Vec2, position: Vec2) -> bool {
+    position.distance(stick_center(screen)) <= STICK_GRAB || button_at(screen, position).is_some()
+}
+
+// Movement held by the knob at `offset` from the center, in eight directions
+// like the keys, a diagonal holds both of its sides
+fn stick_actions(offset: Vec2) -> Vec<Action> {
+    let length = offset.length();
+    if length < DEAD_ZONE * STICK_RADIUS {
+        return vec![];
+    }
+    // half of the 45° sector around each direction
+    let threshold = length * FRAC_PI_8.sin();
+    let mut actions = vec![];
+    if offset.x > threshold {
+        actions.push(Action::MoveRight);
+    }
+    if offset.x < -threshold {
+        actions.push(Action::MoveLeft);
+    }
+    if offset.y > threshold {
+        actions.push(Action::MoveDown);
+    }
+    if offset.y < -threshold {
+        actions.push(Action::MoveUp);
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Vec2 = vec2(800.0, 400.0);
+
+    #[test]
+    fn the_stick_moves_in_eight_directions() {
+        assert!(stick_actions(vec2(10.0, 5.0)).is_empty());
+        assert_eq!(stick_actions(vec2(60.0, 10.0)), [Action::MoveRight]);
+        assert_eq!(stick_actions(vec2(-5.0, -60.0)), [Action::MoveUp]);
+        assert_eq!(
+            stick_actions(vec2(-40.0, 40.0)),
+            [Action::MoveLeft, Action::MoveDown]
+        );
+    }
+
+    #[test]
+    fn buttons_sit_in_the_bottom_right_corner() {
+        let bottom_right = SCREEN - MARGIN - 1.0;
+        assert_eq!(button_at(SCREEN, bottom_right), Some(0));
+        let above = bottom_right - vec2(0.0, BUTTON_SIZE.y + BUTTON_GAP);
+        assert_eq!(button_at(SCREEN, above), Some(1));
+        assert_eq!(button_at(SCREEN, SCREEN / 2.0), None);
+    }
+
+    #[test]
+    fn only_taps_off_the_controls_walk() {
+        assert!(covers(
+            SCREEN,
+            stick_center(SCREEN) + vec2(STICK_RADIUS, 0.0)
+        ));
+        assert!(covers(SCREEN, button_rect(SCREEN, 1).center()));
+        assert!(!covers(SCREEN, SCREEN / 2.0));
+    }
+}
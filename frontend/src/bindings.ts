@@ -0,0 +1,17 @@
+import { set_input_bindings } from "../wasm/autonomous-game";
+
+// localStorage key of the game's input bindings, as JSON
+const STORAGE_KEY = "inputBindings";
+
+// Called by the game whenever the bindings change
+export function saveInputBindings(bindings: string) {
+  localStorage.setItem(STORAGE_KEY, bindings);
+}
+
+// Hands the bindings saved last session to the game, before it reads them
+export function loadInputBindings() {
+  const bindings = localStorage.getItem(STORAGE_KEY);
+  if (bindings) {
+    set_input_bindings(bindings);
+  }
+}
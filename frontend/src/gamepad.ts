@@ -0,0 +1,12 @@
+// Buttons held on the first gamepad connected, bit i for button i of the
+// standard layout. Read by the game once a frame
+export function readGamepadButtons(): number {
+  const pad = navigator.getGamepads().find((pad) => pad?.connected);
+  if (!pad) {
+    return 0;
+  }
+  // only the first 16 buttons have a place in the standard layout
+  return pad.buttons
+    .slice(0, 16)
+    .reduce((held, button, i) => (button.pressed ? held | (1 << i) : held), 0);
+}
@@ -7,6 +7,8 @@ export function update_sui_address(sui_address: string): void;
 export function get_sui_address(): string;
 export function update_is_paid(is_paid: boolean): void;
 export function get_is_paid(): boolean;
+export function set_input_bindings(input_bindings: string): void;
+export function get_input_bindings(): string;
 
 export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembly.Module;
 
@@ -38,6 +40,8 @@ export interface InitOutput {
   readonly get_sui_address: () => [number, number];
   readonly update_is_paid: (a: number) => void;
   readonly get_is_paid: () => number;
+  readonly set_input_bindings: (a: number, b: number) => void;
+  readonly get_input_bindings: () => [number, number];
   readonly set_player_balance: (a: number) => void;
   readonly __wbindgen_export_0: WebAssembly.Table;
   readonly __wbindgen_malloc: (a: number, b: number) => number;
@@ -4,6 +4,8 @@ import {
   requestPaidTransaction,
   emitGameStart,
 } from "../src/wallet";
+import { saveInputBindings } from "../src/bindings";
+import { readGamepadButtons } from "../src/gamepad";
 
 let wasm;
 export const set_wasm = (w) => (wasm = w);
@@ -176,6 +178,35 @@ export function get_is_paid() {
   return ret !== 0;
 }
 
+/**
+ * @param {string} input_bindings
+ */
+export function set_input_bindings(input_bindings) {
+  const ptr0 = passStringToWasm0(
+    input_bindings,
+    wasm.__wbindgen_malloc,
+    wasm.__wbindgen_realloc,
+  );
+  const len0 = WASM_VECTOR_LEN;
+  wasm.set_input_bindings(ptr0, len0);
+}
+
+/**
+ * @returns {string}
+ */
+export function get_input_bindings() {
+  let deferred1_0;
+  let deferred1_1;
+  try {
+    const ret = wasm.get_input_bindings();
+    deferred1_0 = ret[0];
+    deferred1_1 = ret[1];
+    return getStringFromWasm0(ret[0], ret[1]);
+  } finally {
+    wasm.__wbindgen_free(deferred1_0, deferred1_1, 1);
+  }
+}
+
 async function __wbg_load(module, imports) {
   if (typeof Response === "function" && module instanceof Response) {
     if (typeof WebAssembly.instantiateStreaming === "function") {
@@ -224,6 +255,16 @@ function __wbg_get_imports() {
   imports.wbg.__wbg_requestPaidTransaction_f555f8b073f9944b = function () {
     requestPaidTransaction();
   };
+  imports.wbg.__wbg_saveInputBindings_5ce7e52f63547268 = function (
+    arg0,
+    arg1,
+  ) {
+    saveInputBindings(getStringFromWasm0(arg0, arg1));
+  };
+  imports.wbg.__wbg_readGamepadButtons_be640b318d7f27c9 = function () {
+    const ret = readGamepadButtons();
+    return ret;
+  };
   imports.wbg.__wbindgen_init_externref_table = function () {
     const table = wasm.__wbindgen_export_0;
     const offset = table.grow(4);
@@ -27,6 +27,8 @@ export const update_sui_address: (a: number, b: number) => void;
 export const get_sui_address: () => [number, number];
 export const update_is_paid: (a: number) => void;
 export const get_is_paid: () => number;
+export const set_input_bindings: (a: number, b: number) => void;
+export const get_input_bindings: () => [number, number];
 export const set_player_balance: (a: number) => void;
 export const __wbindgen_export_0: WebAssembly.Table;
 export const __wbindgen_malloc: (a: number, b: number) => number;